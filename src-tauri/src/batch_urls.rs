@@ -0,0 +1,161 @@
+use std::collections::HashSet;
+use std::fs;
+
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::download_command::RunCommandParam;
+
+// 複数のURLを1回のyt-dlpで実行するか、URLごとのジョブとしてキューに積むか
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchMode {
+    #[default]
+    Single,
+    Queue,
+}
+
+// URL以外のダウンロードの指定はparamをすべてのURLで共有する
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct BatchDownloadRequest {
+    pub param: RunCommandParam,
+    pub urls: Vec<String>,
+    pub batch_file: Option<String>,
+    pub mode: BatchMode,
+    pub priority: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchUrlError {
+    pub url: String,
+    pub line: Option<usize>, // バッチファイルから読み込んだときの行番号
+    pub message: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchUrls {
+    pub urls: Vec<String>,
+    pub duplicates: Vec<String>,
+    pub errors: Vec<BatchUrlError>,
+}
+
+pub fn validate_url(url: &str) -> Result<String, String> {
+    let parsed = Url::parse(url.trim()).map_err(|_| "URLの形式が不正です".to_string())?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("対応していないスキームです: {}", parsed.scheme()));
+    }
+    if parsed.host_str().is_none() {
+        return Err("ホスト名がありません".into());
+    }
+    Ok(url.trim().to_string())
+}
+
+// yt-dlpの--batch-fileと同じく、#、;、]で始まる行はコメントとして読み飛ばす
+fn batch_file_lines(content: &str) -> impl Iterator<Item = (usize, &str)> {
+    content
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with(['#', ';', ']']))
+}
+
+// 入力されたURLとバッチファイルの内容をまとめ、重複を除いて検証する。エラーはURLごとに返す
+pub fn collect_batch_urls(urls: &[String], batch_file: Option<&str>) -> Result<BatchUrls, String> {
+    let mut candidates = urls
+        .iter()
+        .flat_map(|text| text.split_whitespace())
+        .map(|url| (None, url.to_string()))
+        .collect::<Vec<_>>();
+    if let Some(batch_file) = batch_file.map(str::trim).filter(|path| !path.is_empty()) {
+        let content = fs::read_to_string(batch_file)
+            .map_err(|e| format!("バッチファイルを読み込めません: {}", e))?;
+        candidates
+            .extend(batch_file_lines(&content).map(|(line, url)| (Some(line), url.to_string())));
+    }
+
+    let mut batch = BatchUrls::default();
+    let mut seen = HashSet::new();
+    for (line, url) in candidates {
+        match validate_url(&url) {
+            Ok(url) => {
+                // 末尾の/やホスト名の大文字小文字だけが違うURLも同じものとみなす
+                let key = Url::parse(&url)
+                    .map(|parsed| parsed.as_str().trim_end_matches('/').to_string())
+                    .unwrap_or_else(|_| url.clone());
+                if seen.insert(key) {
+                    batch.urls.push(url);
+                } else {
+                    batch.duplicates.push(url);
+                }
+            }
+            Err(message) => batch.errors.push(BatchUrlError { url, line, message }),
+        }
+    }
+    Ok(batch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dedupes_and_reports_invalid_urls() {
+        let batch = collect_batch_urls(
+            &[
+                "https://www.youtube.com/watch?v=a\nhttps://WWW.youtube.com/watch?v=a".to_string(),
+                "https://example.com/video/ ftp://example.com/a not-a-url".to_string(),
+                "https://example.com/video".to_string(),
+            ],
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            batch.urls,
+            vec![
+                "https://www.youtube.com/watch?v=a",
+                "https://example.com/video/"
+            ]
+        );
+        assert_eq!(batch.duplicates.len(), 2);
+        assert_eq!(
+            batch.errors,
+            vec![
+                BatchUrlError {
+                    url: "ftp://example.com/a".to_string(),
+                    line: None,
+                    message: "対応していないスキームです: ftp".to_string(),
+                },
+                BatchUrlError {
+                    url: "not-a-url".to_string(),
+                    line: None,
+                    message: "URLの形式が不正です".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_batch_file_with_comments() {
+        let path =
+            std::env::temp_dir().join(format!("yt-dlp-gui-batch-test-{}.txt", std::process::id()));
+        fs::write(
+            &path,
+            "\u{feff}# list\nhttps://example.com/1\n\n; skip\n  https://example.com/2  \n-o\n",
+        )
+        .unwrap();
+
+        let batch = collect_batch_urls(&[], path.to_str()).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(
+            batch.urls,
+            vec!["https://example.com/1", "https://example.com/2"]
+        );
+        assert_eq!(batch.errors[0].line, Some(6));
+    }
+}
@@ -1,11 +1,25 @@
-use crate::{config::Settings, download_command::RunCommandParam};
+use crate::{
+    config::{AppState, Settings},
+    download_command::{validate_remote_param, RunCommandParam},
+    download_progress::DownloadProgress,
+    http_client::build_http_client,
+    process_manager::{emit_exit, emit_output, emit_progress, CommandManager},
+};
 use serde::{Deserialize, Serialize};
-use tauri::{Emitter, Window};
+use std::sync::Arc;
+use tauri::{State, Window};
+use tokio::sync::Mutex;
 use tokio::time::{sleep, Duration};
 
 #[derive(Serialize)]
 struct RemoteRunRequest {
     param: RunCommandParam,
+    job_id: u64,
+}
+
+#[derive(Serialize)]
+struct RemoteStopRequest {
+    job_id: Option<u64>,
 }
 
 #[derive(Deserialize)]
@@ -17,7 +31,12 @@ struct RemoteRunResponse {
 #[serde(rename_all = "camelCase")]
 struct RemoteOutputResponse {
     running: bool,
+    // サーバーで実行中のジョブ。古いサーバーは返さない
+    #[serde(default)]
+    job_id: Option<u64>,
     outputs: Vec<RemoteOutputLine>,
+    #[serde(default)]
+    progress: Option<DownloadProgress>,
 }
 
 #[derive(Deserialize)]
@@ -27,20 +46,25 @@ struct RemoteOutputLine {
 }
 
 pub(crate) async fn start_remote_download(
+    command_manager: Arc<Mutex<CommandManager>>,
     param: RunCommandParam,
     settings: &Settings,
     window: Window,
+    job_id: u64,
 ) -> Result<u32, String> {
+    // サーバーでも同じ確認をするが、送信前に分かるものはここで知らせる
+    validate_remote_param(&param)?;
     let server_url = normalize_server_url(&settings.remote_server_url)?;
     let token = settings.remote_auth_token.trim();
     if token.is_empty() {
         return Err("リモートサーバーのトークンが設定されていません".into());
     }
 
-    let response = reqwest::Client::new()
+    let client = build_http_client(settings)?;
+    let response = client
         .post(format!("{}/run", server_url))
         .bearer_auth(token)
-        .json(&RemoteRunRequest { param })
+        .json(&RemoteRunRequest { param, job_id })
         .send()
         .await
         .map_err(|e| format!("リモートサーバーへの接続に失敗しました: {}", e))?;
@@ -58,20 +82,32 @@ pub(crate) async fn start_remote_download(
         .json::<RemoteRunResponse>()
         .await
         .map_err(|e| format!("リモートサーバーの応答を解析できません: {}", e))?;
-    start_remote_output_polling(server_url, token.to_string(), window);
+    start_remote_output_polling(
+        command_manager,
+        client,
+        server_url,
+        token.to_string(),
+        window,
+        job_id,
+    );
     Ok(body.pid)
 }
 
-pub(crate) async fn stop_remote_download(settings: &Settings) -> Result<(), String> {
+// job_idがNoneならサーバーで実行中のジョブを止める
+pub(crate) async fn stop_remote_download(
+    settings: &Settings,
+    job_id: Option<u64>,
+) -> Result<(), String> {
     let server_url = normalize_server_url(&settings.remote_server_url)?;
     let token = settings.remote_auth_token.trim();
     if token.is_empty() {
         return Err("リモートサーバーのトークンが設定されていません".into());
     }
 
-    let response = reqwest::Client::new()
+    let response = build_http_client(settings)?
         .post(format!("{}/stop", server_url))
         .bearer_auth(token)
+        .json(&RemoteStopRequest { job_id })
         .send()
         .await
         .map_err(|e| format!("リモートサーバーへの接続に失敗しました: {}", e))?;
@@ -88,10 +124,18 @@ pub(crate) async fn stop_remote_download(settings: &Settings) -> Result<(), Stri
     Ok(())
 }
 
-fn start_remote_output_polling(server_url: String, token: String, window: Window) {
+fn start_remote_output_polling(
+    command_manager: Arc<Mutex<CommandManager>>,
+    client: reqwest::Client,
+    server_url: String,
+    token: String,
+    window: Window,
+    job_id: u64,
+) {
     tokio::spawn(async move {
-        let client = reqwest::Client::new();
         let mut since = 0_u64;
+        let mut last_progress: Option<DownloadProgress> = None;
+        let mut success = false;
 
         loop {
             let response = client
@@ -100,51 +144,77 @@ fn start_remote_output_polling(server_url: String, token: String, window: Window
                 .send()
                 .await;
             let Ok(response) = response else {
-                let _ = window.emit("process-exit", "リモートサーバーとの接続が切断されました");
+                emit_exit(&window, job_id, "リモートサーバーとの接続が切断されました");
                 break;
             };
             if !response.status().is_success() {
                 let status = response.status();
                 let body = response.text().await.unwrap_or_default();
-                let _ = window.emit(
-                    "process-exit",
+                emit_exit(
+                    &window,
+                    job_id,
                     format!("リモートサーバーがエラーを返しました: {} {}", status, body),
                 );
                 break;
             }
             let output_response = response.json::<RemoteOutputResponse>().await;
             let Ok(output_response) = output_response else {
-                let _ = window.emit("process-exit", "リモートサーバーの応答を解析できません");
+                emit_exit(&window, job_id, "リモートサーバーの応答を解析できません");
                 break;
             };
+            // サーバーが別のジョブを実行しているなら、このジョブは既に終わっている
+            if output_response
+                .job_id
+                .is_some_and(|running_job_id| running_job_id != job_id)
+            {
+                emit_exit(&window, job_id, "プロセス終了");
+                break;
+            }
 
             for output in output_response.outputs {
                 since = output.id + 1;
                 if !output.line.is_empty() {
-                    let _ = window.emit("process-output", output.line);
+                    emit_output(&window, job_id, output.line);
+                }
+            }
+
+            if output_response.progress.is_some() && output_response.progress != last_progress {
+                last_progress = output_response.progress.clone();
+                if let Some(progress) = output_response.progress {
+                    emit_progress(&window, job_id, progress);
                 }
             }
 
             if !output_response.running {
-                let _ = window.emit("process-output", "\n");
-                let _ = window.emit("process-exit", "プロセス終了");
+                emit_output(&window, job_id, "\n");
+                emit_exit(&window, job_id, "プロセス終了");
+                success = true;
                 break;
             }
 
             sleep(Duration::from_millis(500)).await;
         }
+        command_manager
+            .lock()
+            .await
+            .finish_remote_job(job_id, success);
     });
 }
 
 #[tauri::command]
-pub async fn test_remote_server(server_url: String, auth_token: String) -> Result<(), String> {
+pub async fn test_remote_server(
+    app_state: State<'_, AppState>,
+    server_url: String,
+    auth_token: String,
+) -> Result<(), String> {
+    let settings = app_state.settings.lock().await.clone();
     let server_url = normalize_server_url(&server_url)?;
     let token = auth_token.trim();
     if token.is_empty() {
         return Err("トークンが入力されていません".into());
     }
 
-    let response = reqwest::Client::new()
+    let response = build_http_client(&settings)?
         .get(format!("{}/health", server_url))
         .bearer_auth(token)
         .send()
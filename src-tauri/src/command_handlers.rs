@@ -1,16 +1,22 @@
 use crate::{
+    batch_urls::{collect_batch_urls, BatchDownloadRequest, BatchMode, BatchUrls},
     client::remote::{start_remote_download, stop_remote_download},
-    config::AppState,
-    download_command::{build_yt_dlp_args, RunCommandParam},
-    process_manager::CommandManager,
-    tools::resolve_tool_paths,
+    config::{AppState, Settings},
+    destination::validate_destination,
+    download_command::{
+        build_yt_dlp_args, required_ffmpeg_encoder, resolve_save_dir, RunCommandParam,
+    },
+    download_queue::{DownloadQueue, QueueJob},
+    process_manager::{CommandManager, RunningJobInfo, StartedJob},
+    tools::{ensure_ffmpeg_encoder, resolve_settings_tool_paths},
 };
+use serde::Serialize;
 use std::sync::Arc;
 use tauri::State;
 use tauri::Window;
 use tokio::sync::Mutex;
 
-const REMOTE_EXECUTION_TARGET: &str = "remote";
+pub(crate) const REMOTE_EXECUTION_TARGET: &str = "remote";
 
 #[tauri::command]
 pub async fn start_download(
@@ -18,45 +24,183 @@ pub async fn start_download(
     window: tauri::Window,
     param: RunCommandParam,
     app_state: State<'_, AppState>,
-) -> Result<u32, String> {
+) -> Result<StartedJob, String> {
     let settings = app_state.settings.lock().await.clone();
     if settings.execution_target == REMOTE_EXECUTION_TARGET {
-        return start_remote_download(param, &settings, window).await;
+        return start_remote_job(command_manager.inner().clone(), window, param, &settings).await;
     }
 
     let mut manager = command_manager.lock().await;
-    let (yt_dlp_path, _ffmpeg_path, _deno_path) = resolve_tool_paths(
-        settings.use_bundle_tools,
-        &settings.yt_dlp_path,
-        &settings.ffmpeg_path,
-        &settings.deno_path,
+    start_local_download(
+        &mut manager,
+        command_manager.inner().clone(),
+        window,
+        param,
+        &settings,
     )
-    .map_err(|e| format!("ツールパスの解決に失敗しました: {}", e))?;
+    .await
+}
 
-    if yt_dlp_path.trim().is_empty() {
+// リモートのジョブも同時実行数に含め、終了するまでCommandManagerで管理する
+async fn start_remote_job(
+    command_manager: Arc<Mutex<CommandManager>>,
+    window: Window,
+    param: RunCommandParam,
+    settings: &Settings,
+) -> Result<StartedJob, String> {
+    let job_id = command_manager
+        .lock()
+        .await
+        .reserve_remote_job(settings.max_concurrent_downloads as usize)?;
+    match start_remote_download(command_manager.clone(), param, settings, window, job_id).await {
+        Ok(pid) => {
+            command_manager.lock().await.set_remote_pid(job_id, pid);
+            Ok(StartedJob { job_id, pid })
+        }
+        Err(err) => {
+            command_manager.lock().await.release_remote_job(job_id);
+            Err(err)
+        }
+    }
+}
+
+pub(crate) async fn start_local_download(
+    manager: &mut CommandManager,
+    command_manager: Arc<Mutex<CommandManager>>,
+    window: Window,
+    param: RunCommandParam,
+    settings: &Settings,
+) -> Result<StartedJob, String> {
+    let tools = resolve_settings_tool_paths(settings)?;
+
+    if tools.yt_dlp.trim().is_empty() {
         return Err(
             "yt-dlpが見つかりません。ツールをダウンロードするかパスを設定してください。".into(),
         );
     }
 
-    let args = build_yt_dlp_args(param, &settings)?;
+    let recording_limit = param.live.as_ref().and_then(|live| live.recording_limit());
+    if let Some(save_dir) = resolve_save_dir(&param, settings) {
+        validate_destination(&save_dir, settings.min_free_space_mb)?;
+    }
+    let encoder = required_ffmpeg_encoder(&param, settings);
+    let args = build_yt_dlp_args(param, settings, &tools)?;
+    if let Some(encoder) = encoder {
+        ensure_ffmpeg_encoder(&tools.ffmpeg, encoder)?;
+    }
 
     manager
-        .start_command(command_manager.inner().clone(), args, window, &yt_dlp_path)
+        .start_command(
+            command_manager,
+            args,
+            window,
+            &tools.yt_dlp,
+            settings.max_concurrent_downloads as usize,
+            recording_limit,
+        )
         .await
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchDownloadResult {
+    #[serde(flatten)]
+    pub batch: BatchUrls,
+    pub started: Option<StartedJob>,
+    pub queued: Vec<QueueJob>,
+}
+
+// 複数のURLやバッチファイルから、1回の実行か、URLごとのキューのジョブを作る
+#[tauri::command]
+pub async fn start_batch_download(
+    command_manager: State<'_, Arc<Mutex<CommandManager>>>,
+    queue: State<'_, Arc<Mutex<DownloadQueue>>>,
+    window: tauri::Window,
+    app_state: State<'_, AppState>,
+    request: BatchDownloadRequest,
+) -> Result<BatchDownloadResult, String> {
+    let BatchDownloadRequest {
+        param,
+        urls,
+        batch_file,
+        mode,
+        priority,
+    } = request;
+    let batch = collect_batch_urls(&urls, batch_file.as_deref())?;
+    let mut result = BatchDownloadResult {
+        batch,
+        started: None,
+        queued: Vec::new(),
+    };
+    let Some((first_url, rest_urls)) = result.batch.urls.split_first() else {
+        return Ok(result);
+    };
+
+    if mode == BatchMode::Queue {
+        let params = result
+            .batch
+            .urls
+            .iter()
+            .map(|url| RunCommandParam {
+                url: Some(url.clone()),
+                extra_urls: Vec::new(),
+                ..param.clone()
+            })
+            .collect();
+        result.queued = queue.lock().await.enqueue_all(params, priority)?;
+        return Ok(result);
+    }
+
+    let param = RunCommandParam {
+        url: Some(first_url.clone()),
+        extra_urls: rest_urls.to_vec(),
+        ..param
+    };
+    let settings = app_state.settings.lock().await.clone();
+    if settings.execution_target == REMOTE_EXECUTION_TARGET {
+        result.started = Some(
+            start_remote_job(command_manager.inner().clone(), window, param, &settings).await?,
+        );
+        return Ok(result);
+    }
+
+    let mut manager = command_manager.lock().await;
+    result.started = Some(
+        start_local_download(
+            &mut manager,
+            command_manager.inner().clone(),
+            window,
+            param,
+            &settings,
+        )
+        .await?,
+    );
+    Ok(result)
+}
+
 #[tauri::command]
 pub async fn stop_download(
     command_manager: State<'_, Arc<Mutex<CommandManager>>>,
     window: Window,
     app_state: State<'_, AppState>,
+    job_id: Option<u64>,
 ) -> Result<(), String> {
     let settings = app_state.settings.lock().await.clone();
     if settings.execution_target == REMOTE_EXECUTION_TARGET {
-        return stop_remote_download(&settings).await;
+        if let Some(job_id) = job_id {
+            if !command_manager.lock().await.is_remote_job(job_id) {
+                return Err(format!("ジョブ{}は実行されていません", job_id));
+            }
+        }
+        return stop_remote_download(&settings, job_id).await;
     }
 
-    let mut manager = command_manager.lock().await;
-    manager.stop_command(window).await
+    CommandManager::stop_command(command_manager.inner(), job_id, window).await
+}
+
+#[tauri::command]
+pub async fn get_running_downloads(
+    command_manager: State<'_, Arc<Mutex<CommandManager>>>,
+) -> Result<Vec<RunningJobInfo>, String> {
+    Ok(command_manager.lock().await.running_jobs())
 }
@@ -1,25 +1,111 @@
 use dirs::video_dir;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::{fs, path::PathBuf};
 use std::{io::Write, mem};
 use tokio::sync::Mutex;
 
 const SETTINGS_FILENAME: &str = "settings.json";
 const LOCAL_EXECUTION_TARGET: &str = "local";
+pub const FORMAT_STRATEGY_GENERIC: &str = "generic";
+pub const FORMAT_STRATEGY_YOUTUBE_LEGACY: &str = "youtube_legacy";
+pub const AUDIO_CODEC_ORIGINAL: &str = "original";
+pub const ARCHIVE_SCOPE_GLOBAL: &str = "global";
+pub const ARCHIVE_SCOPE_SAVE_DIR: &str = "save_dir";
+pub const CONVERT_MODE_NONE: &str = "none";
+pub const CONVERT_MODE_REMUX: &str = "remux";
+pub const CONVERT_MODE_RECODE: &str = "recode";
+const CONVERT_CONTAINERS: [&str; 6] = ["mp4", "mkv", "mov", "webm", "avi", "flv"];
+// ffmpegの追加引数で使えるエンコーダーと画質のオプション。出力先や形式を増やすものは含めない
+const CONVERT_POSTPROCESSOR_FLAGS: [&str; 20] = [
+    "-c:a",
+    "-c:v",
+    "-b:a",
+    "-b:v",
+    "-crf",
+    "-preset",
+    "-tune",
+    "-profile:v",
+    "-level",
+    "-pix_fmt",
+    "-tag:v",
+    "-q:a",
+    "-q:v",
+    "-maxrate",
+    "-minrate",
+    "-bufsize",
+    "-g",
+    "-r",
+    "-ac",
+    "-ar",
+];
+pub const JS_RUNTIME_REMOTE: &str = "remote";
+pub const JS_RUNTIME_BUNDLED: &str = "bundled";
+pub const JS_RUNTIME_LOCAL: &str = "local";
+pub const JS_RUNTIME_DISABLED: &str = "disabled";
+pub const SITE_RULE_MATCH_DOMAIN: &str = "domain";
+pub const SITE_RULE_MATCH_REGEX: &str = "regex";
+pub const COOKIE_SOURCE_BROWSER: &str = "browser";
+pub const COOKIE_SOURCE_FILE: &str = "file";
+const COOKIE_BROWSERS: [&str; 9] = [
+    "brave", "chrome", "chromium", "edge", "firefox", "opera", "safari", "vivaldi", "whale",
+];
+const COOKIE_KEYRINGS: [&str; 5] = [
+    "basictext",
+    "gnomekeyring",
+    "kwallet",
+    "kwallet5",
+    "kwallet6",
+];
 
 #[cfg(target_os = "windows")]
-fn get_config_root() -> PathBuf {
+pub(crate) fn get_config_root() -> PathBuf {
     let appdata = PathBuf::from(std::env::var("APPDATA").unwrap());
     appdata.join("yt-dlp-GUI")
 }
 
 #[cfg(any(target_os = "linux", target_os = "macos"))]
-fn get_config_root() -> PathBuf {
+pub(crate) fn get_config_root() -> PathBuf {
     let home = PathBuf::from(std::env::var("HOME").unwrap());
     home.join(".yt-dlp-GUI")
 }
 
+// 任意のプログラムの実行や設定ファイル・プラグインの読み込み、保存先の外への読み書きにつながるオプション。
+// --aliasは展開後のオプションを確認できないので使わせない
+fn default_arbitrary_code_denied_flags() -> Vec<String> {
+    [
+        "--exec",
+        "--exec-before-download",
+        "--netrc-cmd",
+        "--config-locations",
+        "--plugin-dirs",
+        "--use-postprocessor",
+        "--downloader",
+        "--external-downloader",
+        "--downloader-args",
+        "--external-downloader-args",
+        "--ffmpeg-location",
+        "-o",
+        "--output",
+        "-P",
+        "--paths",
+        "-a",
+        "--batch-file",
+        "--cookies",
+        "--alias",
+        "--print-to-file",
+        "--postprocessor-args",
+        "--ppa",
+        "--download-archive",
+        "--cache-dir",
+        "--load-info-json",
+    ]
+    .iter()
+    .map(|flag| flag.to_string())
+    .collect()
+}
+
 fn get_default_save_dir() -> String {
     video_dir()
         .unwrap_or_else(|| PathBuf::from("default_videos"))
@@ -50,6 +136,22 @@ pub struct Settings {
     pub remote_server_url: String,
     pub remote_auth_token: String,
     pub server_auth_token: String,
+    pub max_concurrent_downloads: u32,
+    pub video_format: VideoFormatSettings,
+    pub audio_format: AudioFormatSettings,
+    pub embed: EmbedSettings,
+    pub sponsorblock: SponsorBlockSettings,
+    pub arbitrary_code_denied_flags: Vec<String>, // 任意コードモードで使わせないオプション
+    pub download_archive: DownloadArchiveSettings,
+    pub network: NetworkSettings,
+    pub proxy: ProxySettings,
+    pub cookies: CookieSettings,
+    pub convert: ConvertSettings,
+    pub filename: FilenameSettings,
+    pub mode_save_dirs: ModeSaveDirs,
+    pub js_runtime: JsRuntimeSettings,
+    pub min_free_space_mb: u64, // 保存先の空き容量がこれを下回るとダウンロードを始めない。0なら確認しない
+    pub site_rules: Vec<SiteRule>, // 上から順に適用し、後のルールが前のルールを上書きする
     pub yt_dlp_cache: Option<VerifyCache>,
     pub ffmpeg_cache: Option<VerifyCache>,
     pub deno_cache: Option<VerifyCache>,
@@ -74,6 +176,22 @@ impl Default for Settings {
             remote_server_url: "".to_string(),
             remote_auth_token: "".to_string(),
             server_auth_token: "".to_string(),
+            max_concurrent_downloads: 3,
+            video_format: VideoFormatSettings::default(),
+            audio_format: AudioFormatSettings::default(),
+            embed: EmbedSettings::default(),
+            sponsorblock: SponsorBlockSettings::default(),
+            arbitrary_code_denied_flags: default_arbitrary_code_denied_flags(),
+            download_archive: DownloadArchiveSettings::default(),
+            network: NetworkSettings::default(),
+            proxy: ProxySettings::default(),
+            cookies: CookieSettings::default(),
+            convert: ConvertSettings::default(),
+            filename: FilenameSettings::default(),
+            mode_save_dirs: ModeSaveDirs::default(),
+            js_runtime: JsRuntimeSettings::default(),
+            min_free_space_mb: 0,
+            site_rules: Vec::new(),
             yt_dlp_cache: None,
             ffmpeg_cache: None,
             deno_cache: None,
@@ -192,6 +310,86 @@ impl Settings {
         self.write_file();
     }
 
+    pub fn set_max_concurrent_downloads(&mut self, max_concurrent_downloads: u32) {
+        self.max_concurrent_downloads = max_concurrent_downloads.max(1);
+        self.write_file();
+    }
+
+    pub fn set_video_format(&mut self, video_format: VideoFormatSettings) {
+        self.video_format = video_format;
+        self.write_file();
+    }
+
+    pub fn set_audio_format(&mut self, audio_format: AudioFormatSettings) {
+        self.audio_format = audio_format;
+        self.write_file();
+    }
+
+    pub fn set_embed(&mut self, embed: EmbedSettings) {
+        self.embed = embed;
+        self.write_file();
+    }
+
+    pub fn set_sponsorblock(&mut self, sponsorblock: SponsorBlockSettings) {
+        self.sponsorblock = sponsorblock;
+        self.write_file();
+    }
+
+    pub fn set_arbitrary_code_denied_flags(&mut self, denied_flags: Vec<String>) {
+        self.arbitrary_code_denied_flags = denied_flags;
+        self.write_file();
+    }
+
+    pub fn set_download_archive(&mut self, download_archive: DownloadArchiveSettings) {
+        self.download_archive = download_archive;
+        self.write_file();
+    }
+
+    pub fn set_network(&mut self, network: NetworkSettings) {
+        self.network = network;
+        self.write_file();
+    }
+
+    pub fn set_proxy(&mut self, proxy: ProxySettings) {
+        self.proxy = proxy;
+        self.write_file();
+    }
+
+    pub fn set_cookies(&mut self, cookies: CookieSettings) {
+        self.cookies = cookies;
+        self.write_file();
+    }
+
+    pub fn set_convert(&mut self, convert: ConvertSettings) {
+        self.convert = convert;
+        self.write_file();
+    }
+
+    pub fn set_filename(&mut self, filename: FilenameSettings) {
+        self.filename = filename;
+        self.write_file();
+    }
+
+    pub fn set_mode_save_dirs(&mut self, mode_save_dirs: ModeSaveDirs) {
+        self.mode_save_dirs = mode_save_dirs;
+        self.write_file();
+    }
+
+    pub fn set_js_runtime(&mut self, js_runtime: JsRuntimeSettings) {
+        self.js_runtime = js_runtime;
+        self.write_file();
+    }
+
+    pub fn set_min_free_space_mb(&mut self, min_free_space_mb: u64) {
+        self.min_free_space_mb = min_free_space_mb;
+        self.write_file();
+    }
+
+    pub fn set_site_rules(&mut self, site_rules: Vec<SiteRule>) {
+        self.site_rules = site_rules;
+        self.write_file();
+    }
+
     pub fn set_verify_cache(&mut self, program: &str, cache: VerifyCache) {
         match program {
             "yt-dlp" => self.yt_dlp_cache = Some(cache),
@@ -213,14 +411,15 @@ impl Settings {
 }
 
 pub struct AppState {
-    pub settings: Mutex<Settings>,
+    // キューのワーカーとも同じ設定を共有する
+    pub settings: Arc<Mutex<Settings>>,
     pub tool_cache: Mutex<HashMap<String, ToolCacheEntry>>,
 }
 
 impl AppState {
     pub fn new() -> Self {
         Self {
-            settings: Mutex::from(Settings::new()),
+            settings: Arc::new(Mutex::from(Settings::new())),
             tool_cache: Mutex::from(HashMap::new()),
         }
     }
@@ -241,6 +440,638 @@ pub struct VerifyCache {
     pub installed_version: Option<String>,
 }
 
+// 解像度指定モードと、サイトごとのルールで使うフォーマット選択の設定
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct VideoFormatSettings {
+    pub strategy: String, // generic: 高さ上限と並び替えキー, youtube_legacy: 固定のitag
+    pub preferred_vcodec: String, // 空文字列なら指定なし (h264, h265, vp9, av01)
+    pub preferred_container: String, // 空文字列なら指定なし (mp4, webm, mkv)
+    pub max_fps: u32,     // 0なら指定なし
+    pub prefer_hdr: bool,
+}
+
+impl Default for VideoFormatSettings {
+    fn default() -> Self {
+        Self {
+            strategy: FORMAT_STRATEGY_GENERIC.to_string(),
+            preferred_vcodec: "".to_string(),
+            preferred_container: "mp4".to_string(),
+            max_fps: 0,
+            prefer_hdr: false,
+        }
+    }
+}
+
+impl VideoFormatSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.strategy != FORMAT_STRATEGY_GENERIC
+            && self.strategy != FORMAT_STRATEGY_YOUTUBE_LEGACY
+        {
+            return Err(format!("不明なフォーマット選択方式です: {}", self.strategy));
+        }
+        if !matches!(
+            self.preferred_vcodec.as_str(),
+            "" | "h264" | "h265" | "vp9" | "av01"
+        ) {
+            return Err(format!(
+                "対応していない映像コーデックです: {}",
+                self.preferred_vcodec
+            ));
+        }
+        if !matches!(
+            self.preferred_container.as_str(),
+            "" | "mp4" | "webm" | "mkv"
+        ) {
+            return Err(format!(
+                "対応していないコンテナです: {}",
+                self.preferred_container
+            ));
+        }
+        Ok(())
+    }
+}
+
+// 音声のみモードで使う抽出の設定
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AudioFormatSettings {
+    pub codec: String,   // original: 変換しない, mp3, m4a, aac, opus, vorbis, flac, wav
+    pub quality: String, // 0(最高)から10(最低)のVBR品質、または128Kのようなビットレート
+    pub normalize: bool, // ffmpegのloudnormで音量を正規化する
+}
+
+impl Default for AudioFormatSettings {
+    fn default() -> Self {
+        Self {
+            codec: AUDIO_CODEC_ORIGINAL.to_string(),
+            quality: "0".to_string(),
+            normalize: false,
+        }
+    }
+}
+
+impl AudioFormatSettings {
+    pub fn is_conversion(&self) -> bool {
+        self.codec != AUDIO_CODEC_ORIGINAL
+    }
+
+    // flacとwavは可逆なので品質の指定は意味を持たない
+    pub fn is_lossless(&self) -> bool {
+        matches!(self.codec.as_str(), "flac" | "wav")
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !matches!(
+            self.codec.as_str(),
+            AUDIO_CODEC_ORIGINAL | "mp3" | "m4a" | "aac" | "opus" | "vorbis" | "flac" | "wav"
+        ) {
+            return Err(format!("対応していない音声コーデックです: {}", self.codec));
+        }
+        if !is_valid_audio_quality(&self.quality) {
+            return Err(format!("音声の品質の指定が不正です: {}", self.quality));
+        }
+        if self.normalize && !self.is_conversion() {
+            return Err("音量の正規化には変換先のコーデックを指定してください".into());
+        }
+        Ok(())
+    }
+}
+
+fn is_valid_audio_quality(quality: &str) -> bool {
+    if let Ok(level) = quality.parse::<u32>() {
+        return level <= 10;
+    }
+    match quality.strip_suffix(['K', 'k']) {
+        Some(bitrate) => bitrate.parse::<u32>().is_ok_and(|bitrate| bitrate > 0),
+        None => false,
+    }
+}
+
+// 出力ファイルへの埋め込みの設定。ダウンロードごとの指定が無ければこれを使う
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct EmbedSettings {
+    pub metadata: bool,
+    pub thumbnail: bool,
+    pub thumbnail_format: String, // 空文字列なら変換しない (jpg, png, webp)
+    pub chapters: bool,
+    pub subtitles: bool,
+    pub split_chapters: bool, // チャプターごとに別ファイルへ分割する
+}
+
+impl EmbedSettings {
+    pub fn is_enabled(&self) -> bool {
+        self.metadata || self.thumbnail || self.chapters || self.subtitles || self.split_chapters
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !matches!(self.thumbnail_format.as_str(), "" | "jpg" | "png" | "webp") {
+            return Err(format!(
+                "対応していないサムネイル形式です: {}",
+                self.thumbnail_format
+            ));
+        }
+        Ok(())
+    }
+}
+
+const SPONSORBLOCK_CATEGORIES: [&str; 10] = [
+    "all",
+    "sponsor",
+    "intro",
+    "outro",
+    "selfpromo",
+    "preview",
+    "filler",
+    "interaction",
+    "music_offtopic",
+    "chapter",
+];
+
+// SponsorBlockで取得した区間の扱い。enabledがfalseなら何もしない
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SponsorBlockSettings {
+    pub enabled: bool,
+    pub remove: Vec<String>, // 動画から切り取るカテゴリ
+    pub mark: Vec<String>,   // チャプターとして残すカテゴリ
+    pub api_url: String,     // 空文字列ならyt-dlpの既定のAPI
+}
+
+impl Default for SponsorBlockSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            remove: vec!["sponsor".to_string(), "selfpromo".to_string()],
+            mark: vec!["intro".to_string(), "outro".to_string()],
+            api_url: "".to_string(),
+        }
+    }
+}
+
+impl SponsorBlockSettings {
+    pub fn is_active(&self) -> bool {
+        self.enabled && (!self.remove.is_empty() || !self.mark.is_empty())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        // poi_highlightは区間ではなく位置なので、チャプターとしてだけ残せる
+        let invalid_category = self
+            .remove
+            .iter()
+            .find(|category| !SPONSORBLOCK_CATEGORIES.contains(&category.as_str()))
+            .or_else(|| {
+                self.mark.iter().find(|category| {
+                    category.as_str() != "poi_highlight"
+                        && !SPONSORBLOCK_CATEGORIES.contains(&category.as_str())
+                })
+            });
+        if let Some(category) = invalid_category {
+            return Err(format!("不明なSponsorBlockのカテゴリです: {}", category));
+        }
+        let api_url = self.api_url.trim();
+        if !api_url.is_empty()
+            && !api_url.starts_with("http://")
+            && !api_url.starts_with("https://")
+        {
+            return Err("SponsorBlockのAPIのURLはhttp://かhttps://で始めてください".into());
+        }
+        Ok(())
+    }
+}
+
+// ダウンロード済みの動画を記録して、次回以降は飛ばす
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DownloadArchiveSettings {
+    pub enabled: bool,
+    pub scope: String, // global: 設定フォルダに1つ, save_dir: 保存先フォルダごと
+}
+
+impl Default for DownloadArchiveSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            scope: ARCHIVE_SCOPE_GLOBAL.to_string(),
+        }
+    }
+}
+
+// 通信の調整。速度の指定は50Kや4.2Mのようなyt-dlpの表記で、空文字列なら指定しない
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct NetworkSettings {
+    pub rate_limit: String,
+    pub retries: u32,
+    pub fragment_retries: u32,
+    pub concurrent_fragments: u32,
+    pub socket_timeout: u32,    // 秒。0なら指定しない
+    pub throttled_rate: String, // これを下回ったら再取得する
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            rate_limit: "".to_string(),
+            retries: 10,
+            fragment_retries: 10,
+            concurrent_fragments: 1,
+            socket_timeout: 0,
+            throttled_rate: "".to_string(),
+        }
+    }
+}
+
+impl NetworkSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        for (label, rate) in [
+            ("速度制限", &self.rate_limit),
+            ("低速判定", &self.throttled_rate),
+        ] {
+            if !rate.is_empty() && !is_valid_rate(rate) {
+                return Err(format!("{}の指定が不正です: {}", label, rate));
+            }
+        }
+        if !(1..=64).contains(&self.concurrent_fragments) {
+            return Err("同時に取得するフラグメント数は1から64で指定してください".into());
+        }
+        Ok(())
+    }
+}
+
+fn is_valid_rate(rate: &str) -> bool {
+    let number = rate.trim_end_matches(['K', 'k', 'M', 'm', 'G', 'g']);
+    number.len() + 1 >= rate.len() && number.parse::<f64>().is_ok_and(|value| value > 0.0)
+}
+
+// yt-dlpとアプリのHTTP通信で使うプロキシ
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ProxySettings {
+    pub enabled: bool,
+    pub url: String, // http://host:port, https://, socks5://, socks5h://
+    pub username: String,
+    pub password: String,
+    pub no_proxy: Vec<String>, // プロキシを使わないホスト。.example.comならサブドメインも含む
+}
+
+impl ProxySettings {
+    pub fn is_active(&self) -> bool {
+        self.enabled && !self.url.trim().is_empty()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let url = reqwest::Url::parse(self.url.trim())
+            .map_err(|_| format!("プロキシのURLが不正です: {}", self.url))?;
+        if !matches!(url.scheme(), "http" | "https" | "socks5" | "socks5h") {
+            return Err(format!(
+                "対応していないプロキシの種類です: {}",
+                url.scheme()
+            ));
+        }
+        if url.host_str().is_none() {
+            return Err("プロキシのホストが指定されていません".into());
+        }
+        if !url.username().is_empty() || url.password().is_some() {
+            return Err("プロキシの認証情報はユーザー名とパスワードの欄に入力してください".into());
+        }
+        Ok(())
+    }
+}
+
+// cookieの取得元。ブラウザ名はbrowserの設定を使う
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CookieSettings {
+    pub source: String, // browser: ブラウザから読み込む, file: Netscape形式のファイル
+    pub file_path: String, // sourceがfileのときのcookies.txt
+    pub profile: String, // 空文字列なら既定のプロファイル
+    pub container: String, // Firefoxのコンテナ。空文字列なら指定しない
+    pub keyring: String, // Linuxのキーリング。空文字列なら自動
+}
+
+impl Default for CookieSettings {
+    fn default() -> Self {
+        Self {
+            source: COOKIE_SOURCE_BROWSER.to_string(),
+            file_path: "".to_string(),
+            profile: "".to_string(),
+            container: "".to_string(),
+            keyring: "".to_string(),
+        }
+    }
+}
+
+impl CookieSettings {
+    pub fn validate(&self, browser: &str) -> Result<(), String> {
+        match self.source.as_str() {
+            COOKIE_SOURCE_FILE => validate_cookie_file(&self.file_path),
+            COOKIE_SOURCE_BROWSER => {
+                let browser = browser.trim().to_lowercase();
+                if !COOKIE_BROWSERS.contains(&browser.as_str()) {
+                    return Err(format!(
+                        "cookieを読み込めないブラウザです: {} (対応: {})",
+                        browser,
+                        COOKIE_BROWSERS.join(", ")
+                    ));
+                }
+                if !self.keyring.is_empty() && !COOKIE_KEYRINGS.contains(&self.keyring.as_str()) {
+                    return Err(format!("不明なキーリングです: {}", self.keyring));
+                }
+                if !self.container.is_empty() && browser != "firefox" {
+                    return Err("コンテナを指定できるのはFirefoxだけです".into());
+                }
+                Ok(())
+            }
+            _ => Err(format!("不明なcookieの取得元です: {}", self.source)),
+        }
+    }
+
+    // yt-dlpの BROWSER[+KEYRING][:PROFILE][::CONTAINER] の形式
+    pub fn browser_spec(&self, browser: &str) -> String {
+        let mut spec = browser.trim().to_lowercase();
+        if !self.keyring.is_empty() {
+            spec.push_str(&format!("+{}", self.keyring.to_uppercase()));
+        }
+        if !self.profile.trim().is_empty() || !self.container.trim().is_empty() {
+            spec.push_str(&format!(":{}", self.profile.trim()));
+        }
+        if !self.container.trim().is_empty() {
+            spec.push_str(&format!("::{}", self.container.trim()));
+        }
+        spec
+    }
+}
+
+fn validate_cookie_file(file_path: &str) -> Result<(), String> {
+    if file_path.trim().is_empty() {
+        return Err("cookieファイルが指定されていません".into());
+    }
+    let content = fs::read_to_string(file_path.trim())
+        .map_err(|e| format!("cookieファイルを読み込めません: {}", e))?;
+    let first_line = content.lines().next().unwrap_or_default().trim();
+    if first_line != "# Netscape HTTP Cookie File" && first_line != "# HTTP Cookie File" {
+        return Err("Netscape形式のcookieファイルではありません".into());
+    }
+    Ok(())
+}
+
+// ダウンロード後にコンテナを変える設定。remuxは再エンコードせず、recodeはffmpegで変換する
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ConvertSettings {
+    pub mode: String,               // none, remux, recode
+    pub container: String,          // mp4, mkv, mov, webm, avi, flv
+    pub video_codec: String, // recodeのときの映像コーデック。空文字列ならffmpegの既定 (h264, h265, vp9, av1, prores)
+    pub postprocessor_args: String, // ffmpegに追加で渡す引数
+}
+
+impl Default for ConvertSettings {
+    fn default() -> Self {
+        Self {
+            mode: CONVERT_MODE_NONE.to_string(),
+            container: "mp4".to_string(),
+            video_codec: "".to_string(),
+            postprocessor_args: "".to_string(),
+        }
+    }
+}
+
+impl ConvertSettings {
+    pub fn is_active(&self) -> bool {
+        self.mode != CONVERT_MODE_NONE
+    }
+
+    // 映像コーデックに対応するffmpegのエンコーダー
+    pub fn video_encoder(&self) -> Option<&'static str> {
+        match self.video_codec.as_str() {
+            "h264" => Some("libx264"),
+            "h265" => Some("libx265"),
+            "vp9" => Some("libvpx-vp9"),
+            "av1" => Some("libsvtav1"),
+            "prores" => Some("prores_ks"),
+            _ => None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !matches!(
+            self.mode.as_str(),
+            CONVERT_MODE_NONE | CONVERT_MODE_REMUX | CONVERT_MODE_RECODE
+        ) {
+            return Err(format!("不明な変換方法です: {}", self.mode));
+        }
+        if !self.is_active() {
+            return Ok(());
+        }
+        if !CONVERT_CONTAINERS.contains(&self.container.as_str()) {
+            return Err(format!(
+                "対応していないコンテナです: {} (対応: {})",
+                self.container,
+                CONVERT_CONTAINERS.join(", ")
+            ));
+        }
+        if !self.video_codec.is_empty() {
+            if self.mode == CONVERT_MODE_REMUX {
+                return Err("映像コーデックを指定するときは再エンコードを選んでください".into());
+            }
+            if self.video_encoder().is_none() {
+                return Err(format!(
+                    "対応していない映像コーデックです: {}",
+                    self.video_codec
+                ));
+            }
+            let supported = match self.container.as_str() {
+                "webm" => matches!(self.video_codec.as_str(), "vp9" | "av1"),
+                "mov" | "mkv" => true,
+                "mp4" => self.video_codec != "prores",
+                _ => self.video_codec == "h264",
+            };
+            if !supported {
+                return Err(format!(
+                    "{}には{}の映像を格納できません",
+                    self.container, self.video_codec
+                ));
+            }
+        }
+        let args = crate::shell_words::split(&self.postprocessor_args)
+            .map_err(|e| format!("ffmpegの追加引数が不正です: {}", e))?;
+        for pair in args.chunks(2) {
+            let flag = pair[0].as_str();
+            if !CONVERT_POSTPROCESSOR_FLAGS.contains(&flag) {
+                return Err(format!("ffmpegの追加引数に{}は使えません", flag));
+            }
+            // 使えるオプションはどれもファイルを受け取らないので、値は-r 30000/1001のような形もそのまま渡す
+            if pair.len() < 2 {
+                return Err(format!("ffmpegの追加引数{}に値がありません", flag));
+            }
+        }
+        Ok(())
+    }
+}
+
+// 保存するファイル名の整形。restrictはASCIIの英数字などだけに、windows_safeはWindowsで使えない文字を置き換える
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FilenameSettings {
+    pub restrict: bool,
+    pub windows_safe: bool,
+}
+
+// YouTubeのJSチャレンジを解くためのランタイムと、その解決スクリプト(EJS)の取得元
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct JsRuntimeSettings {
+    // remote: GitHubから取得, bundled: yt-dlpに同梱のものを使う, local: component_dirの取得済みのものを使う, disabled: JSランタイムを使わない
+    pub mode: String,
+    pub component_dir: String, // localのときにyt-dlpのキャッシュとして使うフォルダ
+}
+
+impl Default for JsRuntimeSettings {
+    fn default() -> Self {
+        Self {
+            mode: JS_RUNTIME_REMOTE.to_string(),
+            component_dir: "".to_string(),
+        }
+    }
+}
+
+impl JsRuntimeSettings {
+    pub fn uses_deno(&self) -> bool {
+        self.mode != JS_RUNTIME_DISABLED
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self.mode.as_str() {
+            JS_RUNTIME_REMOTE | JS_RUNTIME_BUNDLED | JS_RUNTIME_DISABLED => Ok(()),
+            JS_RUNTIME_LOCAL => {
+                let component_dir = self.component_dir.trim();
+                if component_dir.is_empty() {
+                    return Err("取得済みのコンポーネントのフォルダを指定してください".into());
+                }
+                if !PathBuf::from(component_dir).is_dir() {
+                    return Err(format!(
+                        "コンポーネントのフォルダが存在しません: {}",
+                        component_dir
+                    ));
+                }
+                Ok(())
+            }
+            _ => Err(format!("不明なJSランタイムの設定です: {}", self.mode)),
+        }
+    }
+}
+
+// モードごとの既定の保存先。空文字列ならsave_dirを使う
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ModeSaveDirs {
+    pub video: String, // 通常、解像度指定、コーデックID指定
+    pub audio: String,
+    pub live: String,
+    pub thumbnail: String,
+    pub subtitle: String,
+    pub arbitrary_code: String,
+}
+
+// URLに一致したときにダウンロードの既定値を上書きするルール
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SiteRule {
+    pub name: String,
+    pub enabled: bool,
+    pub match_type: String, // domain: ドメインとそのサブドメイン, regex: URL全体への正規表現
+    pub pattern: String,
+    pub overrides: SiteRuleOverrides,
+}
+
+impl Default for SiteRule {
+    fn default() -> Self {
+        Self {
+            name: "".to_string(),
+            enabled: true,
+            match_type: SITE_RULE_MATCH_DOMAIN.to_string(),
+            pattern: "".to_string(),
+            overrides: SiteRuleOverrides::default(),
+        }
+    }
+}
+
+// Noneの項目は上書きしない
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SiteRuleOverrides {
+    pub use_cookie: Option<bool>,
+    pub browser: Option<String>,
+    pub video_format: Option<VideoFormatSettings>,
+    pub output_template: Option<String>,
+    pub save_dir: Option<String>,
+    pub extra_args: Option<String>, // 任意コードと同じ禁止オプションを適用する
+}
+
+impl SiteRule {
+    pub fn validate(&self) -> Result<(), String> {
+        let pattern = self.pattern.trim();
+        if pattern.is_empty() {
+            return Err(format!("ルール「{}」の条件が空です", self.name));
+        }
+        match self.match_type.as_str() {
+            SITE_RULE_MATCH_DOMAIN => {
+                if pattern.contains(['/', ':', ' ']) {
+                    return Err(format!(
+                        "ルール「{}」にはドメイン名だけを指定してください: {}",
+                        self.name, pattern
+                    ));
+                }
+            }
+            SITE_RULE_MATCH_REGEX => {
+                regex::Regex::new(pattern)
+                    .map_err(|e| format!("ルール「{}」の正規表現が不正です: {}", self.name, e))?;
+            }
+            _ => return Err(format!("不明な一致条件です: {}", self.match_type)),
+        }
+        if let Some(video_format) = &self.overrides.video_format {
+            video_format.validate()?;
+        }
+        if let Some(extra_args) = &self.overrides.extra_args {
+            crate::shell_words::split(extra_args)
+                .map_err(|e| format!("ルール「{}」の追加引数が不正です: {}", self.name, e))?;
+        }
+        Ok(())
+    }
+
+    pub fn matches(&self, url: &str) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let pattern = self.pattern.trim();
+        match self.match_type.as_str() {
+            SITE_RULE_MATCH_DOMAIN => {
+                let Some(host) = reqwest::Url::parse(url.trim())
+                    .ok()
+                    .and_then(|url| url.host_str().map(|host| host.to_lowercase()))
+                else {
+                    return false;
+                };
+                let domain = pattern.trim_start_matches('.').to_lowercase();
+                host == domain || host.ends_with(&format!(".{}", domain))
+            }
+            SITE_RULE_MATCH_REGEX => regex::Regex::new(pattern)
+                .map(|regex| regex.is_match(url.trim()))
+                .unwrap_or(false),
+            _ => false,
+        }
+    }
+}
+
+pub fn matching_site_rules<'a>(rules: &'a [SiteRule], url: &str) -> Vec<&'a SiteRule> {
+    rules.iter().filter(|rule| rule.matches(url)).collect()
+}
+
 pub mod commands {
     use super::*;
     use tauri::State;
@@ -397,4 +1228,199 @@ pub mod commands {
         settings.set_server_auth_token(server_auth_token);
         Ok(())
     }
+
+    #[tauri::command]
+    pub async fn set_max_concurrent_downloads(
+        state: State<'_, AppState>,
+        max_concurrent_downloads: u32,
+    ) -> Result<(), String> {
+        let mut settings = state.settings.lock().await;
+        settings.set_max_concurrent_downloads(max_concurrent_downloads);
+        Ok(())
+    }
+
+    #[tauri::command]
+    pub async fn set_video_format(
+        state: State<'_, AppState>,
+        video_format: VideoFormatSettings,
+    ) -> Result<(), String> {
+        video_format.validate()?;
+        let mut settings = state.settings.lock().await;
+        settings.set_video_format(video_format);
+        Ok(())
+    }
+
+    #[tauri::command]
+    pub async fn set_audio_format(
+        state: State<'_, AppState>,
+        audio_format: AudioFormatSettings,
+    ) -> Result<(), String> {
+        audio_format.validate()?;
+        let mut settings = state.settings.lock().await;
+        settings.set_audio_format(audio_format);
+        Ok(())
+    }
+
+    #[tauri::command]
+    pub async fn set_embed(state: State<'_, AppState>, embed: EmbedSettings) -> Result<(), String> {
+        embed.validate()?;
+        let mut settings = state.settings.lock().await;
+        settings.set_embed(embed);
+        Ok(())
+    }
+
+    #[tauri::command]
+    pub async fn set_sponsorblock(
+        state: State<'_, AppState>,
+        sponsorblock: SponsorBlockSettings,
+    ) -> Result<(), String> {
+        sponsorblock.validate()?;
+        let mut settings = state.settings.lock().await;
+        settings.set_sponsorblock(sponsorblock);
+        Ok(())
+    }
+
+    #[tauri::command]
+    pub async fn set_arbitrary_code_denied_flags(
+        state: State<'_, AppState>,
+        denied_flags: Vec<String>,
+    ) -> Result<(), String> {
+        let denied_flags = denied_flags
+            .into_iter()
+            .map(|flag| flag.trim().to_string())
+            .filter(|flag| !flag.is_empty())
+            .collect::<Vec<_>>();
+        if let Some(flag) = denied_flags.iter().find(|flag| !flag.starts_with('-')) {
+            return Err(format!("オプションは-で始めてください: {}", flag));
+        }
+        let mut settings = state.settings.lock().await;
+        settings.set_arbitrary_code_denied_flags(denied_flags);
+        Ok(())
+    }
+
+    #[tauri::command]
+    pub async fn set_download_archive(
+        state: State<'_, AppState>,
+        download_archive: DownloadArchiveSettings,
+    ) -> Result<(), String> {
+        if download_archive.scope != ARCHIVE_SCOPE_GLOBAL
+            && download_archive.scope != ARCHIVE_SCOPE_SAVE_DIR
+        {
+            return Err(format!(
+                "不明なアーカイブの保存先です: {}",
+                download_archive.scope
+            ));
+        }
+        let mut settings = state.settings.lock().await;
+        settings.set_download_archive(download_archive);
+        Ok(())
+    }
+
+    #[tauri::command]
+    pub async fn set_network(
+        state: State<'_, AppState>,
+        network: NetworkSettings,
+    ) -> Result<(), String> {
+        network.validate()?;
+        let mut settings = state.settings.lock().await;
+        settings.set_network(network);
+        Ok(())
+    }
+
+    #[tauri::command]
+    pub async fn set_proxy(state: State<'_, AppState>, proxy: ProxySettings) -> Result<(), String> {
+        proxy.validate()?;
+        let mut settings = state.settings.lock().await;
+        settings.set_proxy(proxy);
+        Ok(())
+    }
+
+    #[tauri::command]
+    pub async fn set_cookies(
+        state: State<'_, AppState>,
+        cookies: CookieSettings,
+    ) -> Result<(), String> {
+        let mut settings = state.settings.lock().await;
+        cookies.validate(&settings.browser)?;
+        settings.set_cookies(cookies);
+        Ok(())
+    }
+
+    #[tauri::command]
+    pub async fn set_filename(
+        state: State<'_, AppState>,
+        filename: FilenameSettings,
+    ) -> Result<(), String> {
+        let mut settings = state.settings.lock().await;
+        settings.set_filename(filename);
+        Ok(())
+    }
+
+    #[tauri::command]
+    pub async fn set_mode_save_dirs(
+        state: State<'_, AppState>,
+        mode_save_dirs: ModeSaveDirs,
+    ) -> Result<(), String> {
+        let mut settings = state.settings.lock().await;
+        settings.set_mode_save_dirs(mode_save_dirs);
+        Ok(())
+    }
+
+    #[tauri::command]
+    pub async fn set_js_runtime(
+        state: State<'_, AppState>,
+        js_runtime: JsRuntimeSettings,
+    ) -> Result<(), String> {
+        js_runtime.validate()?;
+        let mut settings = state.settings.lock().await;
+        settings.set_js_runtime(js_runtime);
+        Ok(())
+    }
+
+    #[tauri::command]
+    pub async fn set_min_free_space_mb(
+        state: State<'_, AppState>,
+        min_free_space_mb: u64,
+    ) -> Result<(), String> {
+        let mut settings = state.settings.lock().await;
+        settings.set_min_free_space_mb(min_free_space_mb);
+        Ok(())
+    }
+
+    #[tauri::command]
+    pub async fn set_site_rules(
+        state: State<'_, AppState>,
+        site_rules: Vec<SiteRule>,
+    ) -> Result<(), String> {
+        for rule in &site_rules {
+            rule.validate()?;
+        }
+        let mut settings = state.settings.lock().await;
+        settings.set_site_rules(site_rules);
+        Ok(())
+    }
+
+    // 指定したURLに適用されるルールを適用順に返す
+    #[tauri::command]
+    pub async fn match_site_rules(
+        state: State<'_, AppState>,
+        url: String,
+    ) -> Result<Vec<SiteRule>, String> {
+        let settings = state.settings.lock().await;
+        Ok(matching_site_rules(&settings.site_rules, &url)
+            .into_iter()
+            .cloned()
+            .collect())
+    }
+
+    #[tauri::command]
+    pub async fn set_convert(
+        state: State<'_, AppState>,
+        convert: ConvertSettings,
+    ) -> Result<(), String> {
+        convert.validate()?;
+        let mut settings = state.settings.lock().await;
+        settings.set_convert(convert);
+        Ok(())
+    }
 }
@@ -0,0 +1,101 @@
+use std::fs;
+use std::path::Path;
+
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn available_space(dir: &Path) -> Result<u64, String> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(dir.as_os_str().as_bytes())
+        .map_err(|_| "保存先のパスに使えない文字が含まれています".to_string())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(format!(
+            "保存先の空き容量を取得できません: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(target_os = "windows")]
+fn available_space(dir: &Path) -> Result<u64, String> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let path = dir
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect::<Vec<u16>>();
+    let mut available = 0u64;
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            path.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        return Err(format!(
+            "保存先の空き容量を取得できません: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(available)
+}
+
+// 実際にファイルを作って消し、書き込めるかを確かめる
+fn ensure_writable(dir: &Path) -> Result<(), String> {
+    let probe = dir.join(format!(".yt-dlp-gui-write-test-{}", std::process::id()));
+    fs::write(&probe, b"")
+        .map_err(|e| format!("保存先に書き込めません: {} ({})", dir.display(), e))?;
+    let _ = fs::remove_file(&probe);
+    Ok(())
+}
+
+// ダウンロードを始める前に、保存先が存在し、書き込めて、空き容量が足りるかを確認する
+pub fn validate_destination(dir: &str, min_free_space_mb: u64) -> Result<(), String> {
+    let dir = Path::new(dir.trim());
+    if dir.as_os_str().is_empty() {
+        return Err("保存先が指定されていません".into());
+    }
+    if !dir.is_dir() {
+        return Err(format!("保存先のフォルダが存在しません: {}", dir.display()));
+    }
+    ensure_writable(dir)?;
+    if min_free_space_mb == 0 {
+        return Ok(());
+    }
+    let available = available_space(dir)?;
+    if available < min_free_space_mb.saturating_mul(BYTES_PER_MB) {
+        return Err(format!(
+            "保存先の空き容量が不足しています: {} (残り{}MB、必要{}MB)",
+            dir.display(),
+            available / BYTES_PER_MB,
+            min_free_space_mb
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_existing_writable_directory() {
+        let dir = std::env::temp_dir();
+
+        assert!(validate_destination(dir.to_str().unwrap(), 0).is_ok());
+        assert!(validate_destination(dir.to_str().unwrap(), u64::MAX)
+            .unwrap_err()
+            .starts_with("保存先の空き容量が不足しています"));
+        assert!(validate_destination("/nonexistent/yt-dlp-gui", 0)
+            .unwrap_err()
+            .starts_with("保存先のフォルダが存在しません"));
+    }
+}
@@ -0,0 +1,208 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{get_config_root, DownloadArchiveSettings, Settings, ARCHIVE_SCOPE_SAVE_DIR};
+use crate::download_command::{resolve_save_dir, RunCommandParam};
+
+const GLOBAL_ARCHIVE_FILENAME: &str = "download-archive.txt";
+const SAVE_DIR_ARCHIVE_FILENAME: &str = ".yt-dlp-archive.txt";
+
+// yt-dlpのアーカイブは「抽出器名 動画ID」を1行ずつ記録する
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveEntry {
+    pub extractor: String,
+    pub id: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadArchive {
+    pub path: String,
+    pub total: usize,
+    pub entries: Vec<ArchiveEntry>,
+}
+
+// save_dirはモードやサイトごとのルールを反映した、実際に保存するフォルダ
+pub fn archive_path(archive: &DownloadArchiveSettings, save_dir: &str) -> PathBuf {
+    if archive.scope == ARCHIVE_SCOPE_SAVE_DIR {
+        Path::new(save_dir).join(SAVE_DIR_ARCHIVE_FILENAME)
+    } else {
+        get_config_root().join(GLOBAL_ARCHIVE_FILENAME)
+    }
+}
+
+// ダウンロードと同じ保存先を求め、yt-dlpが書き込むアーカイブを対象にする
+fn resolve_archive_path(settings: &Settings, param: &RunCommandParam) -> PathBuf {
+    let save_dir = resolve_save_dir(param, settings).unwrap_or_else(|| settings.save_dir.clone());
+    archive_path(&settings.download_archive, &save_dir)
+}
+
+fn parse_entries(content: &str) -> Vec<ArchiveEntry> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (extractor, id) = line.trim().split_once(' ')?;
+            Some(ArchiveEntry {
+                extractor: extractor.to_string(),
+                id: id.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+fn read_entries(path: &Path) -> Result<Vec<ArchiveEntry>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("アーカイブの読み込みに失敗しました: {}", e))?;
+    Ok(parse_entries(&content))
+}
+
+// 抽出器名か動画IDに大文字小文字を区別せず部分一致するものを返す
+fn search_entries(entries: Vec<ArchiveEntry>, query: &str) -> Vec<ArchiveEntry> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return entries;
+    }
+    entries
+        .into_iter()
+        .filter(|entry| {
+            entry.extractor.to_lowercase().contains(&query)
+                || entry.id.to_lowercase().contains(&query)
+        })
+        .collect()
+}
+
+fn remove_entries(path: &Path, targets: &[ArchiveEntry]) -> Result<usize, String> {
+    let entries = read_entries(path)?;
+    let before = entries.len();
+    let remaining = entries
+        .into_iter()
+        .filter(|entry| {
+            !targets.iter().any(|target| {
+                target.extractor.eq_ignore_ascii_case(&entry.extractor) && target.id == entry.id
+            })
+        })
+        .collect::<Vec<_>>();
+    let removed = before - remaining.len();
+    if removed == 0 {
+        return Ok(0);
+    }
+
+    let content = remaining
+        .iter()
+        .map(|entry| format!("{} {}\n", entry.extractor, entry.id))
+        .collect::<String>();
+    fs::write(path, content).map_err(|e| format!("アーカイブの書き込みに失敗しました: {}", e))?;
+    Ok(removed)
+}
+
+pub mod commands {
+    use super::*;
+    use crate::config::AppState;
+    use tauri::State;
+
+    #[tauri::command]
+    pub async fn get_download_archive(
+        state: State<'_, AppState>,
+        param: RunCommandParam,
+        query: Option<String>,
+    ) -> Result<DownloadArchive, String> {
+        let settings = state.settings.lock().await.clone();
+        let path = resolve_archive_path(&settings, &param);
+        let entries = read_entries(&path)?;
+        let total = entries.len();
+        Ok(DownloadArchive {
+            path: path.to_string_lossy().to_string(),
+            total,
+            entries: search_entries(entries, &query.unwrap_or_default()),
+        })
+    }
+
+    #[tauri::command]
+    pub async fn remove_download_archive_entries(
+        state: State<'_, AppState>,
+        param: RunCommandParam,
+        entries: Vec<ArchiveEntry>,
+    ) -> Result<usize, String> {
+        let settings = state.settings.lock().await.clone();
+        remove_entries(&resolve_archive_path(&settings, &param), &entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(extractor: &str, id: &str) -> ArchiveEntry {
+        ArchiveEntry {
+            extractor: extractor.to_string(),
+            id: id.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_and_searches_entries() {
+        let entries = parse_entries("youtube abcDEF\n\nniconico sm9\nyoutube xyz\n");
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            search_entries(entries.clone(), "ABC"),
+            vec![entry("youtube", "abcDEF")]
+        );
+        assert_eq!(
+            search_entries(entries, "nico"),
+            vec![entry("niconico", "sm9")]
+        );
+    }
+
+    #[test]
+    fn resolves_archive_in_mode_save_dir() {
+        let mut settings = Settings {
+            save_dir: "C:/downloads".to_string(),
+            ..Settings::default()
+        };
+        settings.download_archive.scope = ARCHIVE_SCOPE_SAVE_DIR.to_string();
+        settings.mode_save_dirs.audio = "C:/music".to_string();
+        let param = RunCommandParam {
+            url: Some("https://example.com/watch".to_string()),
+            kind: crate::download_command::DownloadMode::AudioOnly,
+            ..RunCommandParam::default()
+        };
+
+        assert_eq!(
+            resolve_archive_path(&settings, &param),
+            Path::new("C:/music").join(SAVE_DIR_ARCHIVE_FILENAME)
+        );
+        assert_eq!(
+            resolve_archive_path(
+                &settings,
+                &RunCommandParam {
+                    save_dir: Some("C:/once".to_string()),
+                    ..param
+                }
+            ),
+            Path::new("C:/once").join(SAVE_DIR_ARCHIVE_FILENAME)
+        );
+    }
+
+    #[test]
+    fn removes_selected_entries() {
+        let path = std::env::temp_dir().join(format!(
+            "yt-dlp-gui-archive-test-{}.txt",
+            std::process::id()
+        ));
+        fs::write(&path, "youtube a\nyoutube b\nniconico sm9\n").unwrap();
+
+        let removed = remove_entries(&path, &[entry("Youtube", "b")]).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(removed, 1);
+        assert_eq!(content, "youtube a\nniconico sm9\n");
+    }
+}
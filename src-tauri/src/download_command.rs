@@ -1,7 +1,20 @@
-use crate::config::Settings;
+use crate::batch_urls::validate_url;
+use crate::config::{
+    matching_site_rules, AudioFormatSettings, ConvertSettings, CookieSettings, EmbedSettings,
+    FilenameSettings, JsRuntimeSettings, ModeSaveDirs, NetworkSettings, ProxySettings, Settings,
+    SponsorBlockSettings, VideoFormatSettings, CONVERT_MODE_RECODE, CONVERT_MODE_REMUX,
+    COOKIE_SOURCE_FILE, FORMAT_STRATEGY_YOUTUBE_LEGACY, JS_RUNTIME_LOCAL, JS_RUNTIME_REMOTE,
+};
+use crate::download_archive::archive_path;
+use crate::download_progress::progress_template_args;
+use crate::http_client::{bypasses_proxy, proxy_url};
+use crate::shell_words;
+use crate::timestamp;
+use crate::tools::ToolPaths;
 use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Default, Deserialize, Serialize)]
 pub struct RunCommandParam {
     pub url: Option<String>,
     pub kind: DownloadMode,
@@ -12,11 +25,279 @@ pub struct RunCommandParam {
     pub end_time: Option<String>,
     pub is_cookie: bool,
     pub arbitrary_code: Option<String>,
+    #[serde(default)]
+    pub resume: bool, // 中断されたジョブを--continueで再開する
+    #[serde(default)]
+    pub embed: Option<EmbedSettings>, // 指定が無ければ設定の既定値を使う
+    #[serde(default)]
+    pub sponsorblock: Option<SponsorBlockSettings>, // 指定が無ければ設定の既定値を使う
+    #[serde(default)]
+    pub subtitles: Option<SubtitleOptions>, // 指定が無ければsubtitle_langの自動字幕を使う
+    #[serde(default)]
+    pub cookies: Option<CookieSettings>, // 指定が無ければ設定の既定値を使う
+    #[serde(default)]
+    pub sections: Vec<TimeRange>, // start_time/end_timeに加えて切り出す区間
+    #[serde(default)]
+    pub chapter_sections: Vec<String>, // タイトルが正規表現に一致するチャプターを切り出す
+    #[serde(default)]
+    pub force_keyframes_at_cuts: bool, // 再エンコードして区間の境界を正確にする
+    #[serde(default)]
+    pub live: Option<LiveRecordingOptions>,
+    #[serde(default)]
+    pub convert: Option<ConvertSettings>,
+    #[serde(default)]
+    pub extra_urls: Vec<String>, // 同じyt-dlpの実行でまとめてダウンロードする追加のURL
+    #[serde(default)]
+    pub playlist: Option<PlaylistOptions>,
+    #[serde(default)]
+    pub save_dir: Option<String>, // 空ならモードごとの既定フォルダかsave_dirに保存する
 }
 
-#[derive(Clone, Copy, Deserialize, Serialize)]
+// 配信やプレミア公開の開始を待って録画し、長さか終了時刻で打ち切る
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LiveRecordingOptions {
+    pub wait_for_video: bool,
+    pub retry_interval_secs: u64,       // 開始を待つ間の再確認の間隔
+    pub max_duration_secs: Option<u64>, // 録画が始まってからの長さの上限
+    pub end_at: Option<u64>,            // UNIX時間(秒)で指定する終了時刻
+}
+
+impl Default for LiveRecordingOptions {
+    fn default() -> Self {
+        Self {
+            wait_for_video: false,
+            retry_interval_secs: 60,
+            max_duration_secs: None,
+            end_at: None,
+        }
+    }
+}
+
+// 再生リストから保存する項目の選び方。指定があれば動画のURLでも再生リスト全体を対象にする
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PlaylistOptions {
+    pub items: String, // --playlist-itemsの形式 (例: 1-5,8,-3:)
+    pub reverse: bool,
+    pub max_downloads: Option<u32>,
+    pub date_after: String, // YYYYMMDD、またはtoday-2weeksのような相対指定
+    pub date_before: String,
+    pub min_duration_secs: Option<u64>,
+    pub max_duration_secs: Option<u64>,
+    pub min_views: Option<u64>,
+    pub max_views: Option<u64>,
+    pub indexed_folder: bool, // 再生リスト名のフォルダに番号付きで保存する
+}
+
+impl PlaylistOptions {
+    fn validate(&self) -> Result<(), String> {
+        let items = self.items.trim();
+        if !items
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, ',' | '-' | ':'))
+        {
+            return Err(format!("再生リストの項目の指定が不正です: {}", items));
+        }
+        if self.max_downloads == Some(0) {
+            return Err("最大ダウンロード数は1以上にしてください".into());
+        }
+        for date in [&self.date_after, &self.date_before] {
+            if !is_valid_date(date.trim()) {
+                return Err(format!("日付の形式が不正です: {}", date.trim()));
+            }
+        }
+        let is_reversed =
+            |min: Option<u64>, max: Option<u64>| min.zip(max).is_some_and(|(min, max)| min > max);
+        if is_reversed(self.min_duration_secs, self.max_duration_secs) {
+            return Err("再生時間の下限が上限を超えています".into());
+        }
+        if is_reversed(self.min_views, self.max_views) {
+            return Err("再生回数の下限が上限を超えています".into());
+        }
+        Ok(())
+    }
+
+    // yt-dlpの--match-filtersの条件。複数の条件は&でつなぐとすべてを満たすものだけになる
+    fn match_filter(&self) -> Option<String> {
+        let conditions = [
+            self.min_duration_secs
+                .map(|secs| format!("duration>={}", secs)),
+            self.max_duration_secs
+                .map(|secs| format!("duration<={}", secs)),
+            self.min_views.map(|views| format!("view_count>={}", views)),
+            self.max_views.map(|views| format!("view_count<={}", views)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        (!conditions.is_empty()).then(|| conditions.join(" & "))
+    }
+}
+
+// yt-dlpの--dateafterと--datebeforeが受け付ける形式
+fn is_valid_date(date: &str) -> bool {
+    if date.is_empty() || (date.len() == 8 && date.chars().all(|c| c.is_ascii_digit())) {
+        return true;
+    }
+    let Some(offset) = ["now", "today", "yesterday"]
+        .iter()
+        .find_map(|base| date.strip_prefix(base))
+    else {
+        return false;
+    };
+    let Some(offset) = offset.strip_prefix(['-', '+']) else {
+        return offset.is_empty();
+    };
+    let unit_start = offset
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(offset.len());
+    let unit = offset[unit_start..].trim_end_matches('s');
+    unit_start > 0 && matches!(unit, "day" | "week" | "month" | "year")
+}
+
+fn playlist_args(kind: DownloadMode, playlist: &PlaylistOptions) -> Vec<String> {
+    let mut args = Vec::new();
+    if matches!(
+        kind,
+        DownloadMode::ListFormats | DownloadMode::ArbitraryCode
+    ) {
+        return args;
+    }
+    args.push("--yes-playlist".to_string());
+    let items = playlist.items.trim();
+    if !items.is_empty() {
+        args.push("--playlist-items".to_string());
+        args.push(items.to_string());
+    }
+    if playlist.reverse {
+        args.push("--playlist-reverse".to_string());
+    }
+    if let Some(max_downloads) = playlist.max_downloads {
+        args.push("--max-downloads".to_string());
+        args.push(max_downloads.to_string());
+    }
+    for (flag, date) in [
+        ("--dateafter", &playlist.date_after),
+        ("--datebefore", &playlist.date_before),
+    ] {
+        if !date.trim().is_empty() {
+            args.push(flag.to_string());
+            args.push(date.trim().to_string());
+        }
+    }
+    if let Some(match_filter) = playlist.match_filter() {
+        args.push("--match-filters".to_string());
+        args.push(match_filter);
+    }
+    args
+}
+
+// 再生リストの項目を「再生リスト名/001 - タイトル.拡張子」のように保存する
+fn playlist_output_name(output_name: &str) -> String {
+    let output_name = output_name.trim();
+    let file_name = if output_name.is_empty() {
+        "%(title)s.%(ext)s"
+    } else {
+        output_name
+    };
+    format!(
+        "%(playlist_title,playlist_id|playlist)s/%(playlist_index|0)03d - {}",
+        file_name
+    )
+}
+
+pub struct RecordingLimit {
+    pub max_duration: Option<Duration>,
+    pub end_at: Option<SystemTime>,
+}
+
+impl LiveRecordingOptions {
+    fn validate(&self) -> Result<(), String> {
+        if self.wait_for_video && self.retry_interval_secs == 0 {
+            return Err("再確認の間隔は1秒以上にしてください".into());
+        }
+        if self.max_duration_secs == Some(0) {
+            return Err("録画時間の上限は1秒以上にしてください".into());
+        }
+        if let Some(end_at) = self.end_at {
+            if UNIX_EPOCH + Duration::from_secs(end_at) <= SystemTime::now() {
+                return Err("終了時刻が過去になっています".into());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn recording_limit(&self) -> Option<RecordingLimit> {
+        if self.max_duration_secs.is_none() && self.end_at.is_none() {
+            return None;
+        }
+        Some(RecordingLimit {
+            max_duration: self.max_duration_secs.map(Duration::from_secs),
+            end_at: self
+                .end_at
+                .map(|end_at| UNIX_EPOCH + Duration::from_secs(end_at)),
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TimeRange {
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubtitleSource {
+    Manual,
+    #[default]
+    Auto,
+    Both, // 投稿者の字幕があればそちらを優先する
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SubtitleOptions {
+    pub source: SubtitleSource,
+    pub languages: Vec<String>,
+    pub convert: String, // 空文字列なら変換しない (srt, ass, vtt, lrc)
+    pub embed: bool,     // 字幕だけでなく動画も保存して埋め込む
+}
+
+impl SubtitleOptions {
+    // 以前のsubtitle_langはカンマ区切りで複数の言語を指定できる
+    fn from_lang_list(subtitle_lang: &str) -> Self {
+        Self {
+            languages: subtitle_lang
+                .split(',')
+                .map(|lang| lang.trim().to_string())
+                .filter(|lang| !lang.is_empty())
+                .collect(),
+            ..Self::default()
+        }
+    }
+
+    fn requires_ffmpeg(&self) -> bool {
+        self.embed || !self.convert.is_empty()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.languages.iter().all(|lang| lang.trim().is_empty()) {
+            return Err("字幕言語が指定されていません".into());
+        }
+        if !matches!(self.convert.as_str(), "" | "srt" | "ass" | "vtt" | "lrc") {
+            return Err(format!("対応していない字幕形式です: {}", self.convert));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Default, Deserialize, Serialize)]
 #[serde(try_from = "i32", into = "i32")]
 pub enum DownloadMode {
+    #[default]
     Normal,
     AudioOnly,
     Video1080p,
@@ -78,92 +359,474 @@ impl From<DownloadMode> for i32 {
 pub(crate) fn build_yt_dlp_args(
     param: RunCommandParam,
     settings: &Settings,
+    tools: &ToolPaths,
 ) -> Result<Vec<String>, String> {
+    let (param, settings, site_rule) = effective_download_settings(param, settings);
+    let settings = &settings;
     let url = param.url.unwrap_or_default();
+    // 先頭の引数になるので、追加のURLと同じく-で始まる値をオプションとして渡さない
+    let url = if url.trim().is_empty() {
+        url
+    } else {
+        validate_url(&url)?
+    };
     let codec_id = param.codec_id.unwrap_or_default();
-    let subtitle_lang = param.subtitle_lang.unwrap_or_default();
+    let subtitles = param.subtitles.unwrap_or_else(|| {
+        SubtitleOptions::from_lang_list(&param.subtitle_lang.unwrap_or_default())
+    });
     let output_name = param.output_name.unwrap_or_default();
     let arbitrary_code = param.arbitrary_code.unwrap_or_default();
     let start_time = param.start_time.unwrap_or_default();
     let end_time = param.end_time.unwrap_or_default();
+    let download_sections = build_download_sections(
+        &start_time,
+        &end_time,
+        &param.sections,
+        &param.chapter_sections,
+    )?;
 
-    let output_file_name = if output_name.trim().is_empty() {
-        "%(title)s.%(ext)s".to_string()
-    } else {
-        output_name
+    if let Some(playlist) = &param.playlist {
+        playlist.validate()?;
+    }
+    let output_name = match &param.playlist {
+        Some(playlist) if playlist.indexed_folder => playlist_output_name(&output_name),
+        _ => output_name,
+    };
+    let save_path = confined_output_path(&settings.save_dir, &output_name)?;
+    let embed = param.embed.as_ref().unwrap_or(&settings.embed);
+    embed.validate()?;
+    let sponsorblock = param
+        .sponsorblock
+        .as_ref()
+        .unwrap_or(&settings.sponsorblock);
+    sponsorblock.validate()?;
+    // ffmpegの追加引数は出力ファイルを増やせるので、ダウンロードごとの指定ではなく設定の値だけを使う
+    let convert = &ConvertSettings {
+        postprocessor_args: settings.convert.postprocessor_args.clone(),
+        ..param
+            .convert
+            .clone()
+            .unwrap_or_else(|| settings.convert.clone())
+    };
+    convert.validate()?;
+    let modifies_output = (downloads_media(param.kind)
+        && (embed.is_enabled() || sponsorblock.is_active()))
+        || (converts_video(param.kind) && convert.is_active());
+    if requires_ffmpeg(
+        param.kind,
+        &codec_id,
+        !download_sections.is_empty() || modifies_output,
+        &settings.audio_format,
+        &subtitles,
+    ) && tools.ffmpeg.trim().is_empty()
+    {
+        return Err(
+            "このモードにはffmpegが必要です。ツールをダウンロードするかパスを設定してください。"
+                .into(),
+        );
+    }
+
+    let format = FormatSelection {
+        codec_id: &codec_id,
+        site_format: site_rule.video_format.as_ref(),
     };
-    let save_path = format!("{}/{}", settings.save_dir, output_file_name);
     let mut args = args_for_mode(
         param.kind,
         &url,
         &save_path,
-        &codec_id,
-        &subtitle_lang,
+        &format,
+        &subtitles,
         &arbitrary_code,
+        settings,
     )?;
 
-    if let Some(download_section) = build_download_section(&start_time, &end_time) {
+    for extra_url in &param.extra_urls {
+        // -で始まる文字列をオプションとして解釈させないよう、URLとして検証してから渡す
+        let extra_url = validate_url(extra_url).map_err(|e| format!("{}: {}", extra_url, e))?;
+        args.push(extra_url);
+    }
+
+    for download_section in &download_sections {
         args.push("--download-sections".to_string());
-        args.push(download_section);
+        args.push(download_section.clone());
+    }
+    if param.force_keyframes_at_cuts && !download_sections.is_empty() {
+        args.push("--force-keyframes-at-cuts".to_string());
+    }
+
+    if let Some(live) = &param.live {
+        live.validate()?;
+        if live.wait_for_video && downloads_media(param.kind) {
+            args.push("--wait-for-video".to_string());
+            args.push(live.retry_interval_secs.to_string());
+        }
+    }
+
+    if let Some(playlist) = &param.playlist {
+        args.extend(playlist_args(param.kind, playlist));
+    }
+    args.extend(filename_args(param.kind, &settings.filename));
+    args.extend(embed_args(param.kind, embed));
+    args.extend(sponsorblock_args(param.kind, sponsorblock));
+    args.extend(convert_args(param.kind, convert)?);
+
+    if settings.download_archive.enabled && downloads_media(param.kind) {
+        args.push("--download-archive".to_string());
+        args.push(
+            archive_path(&settings.download_archive, &settings.save_dir)
+                .to_string_lossy()
+                .to_string(),
+        );
+    }
+
+    let cookies = param.cookies.as_ref().unwrap_or(&settings.cookies);
+    args.extend(cookie_args(param.is_cookie, &settings.browser, cookies)?);
+    args.extend(network_args(&settings.network)?);
+    args.extend(proxy_args(&settings.proxy, &url)?);
+
+    for extra_args in &site_rule.extra_args {
+        args.extend(site_rule_extra_args(
+            extra_args,
+            &settings.arbitrary_code_denied_flags,
+        )?);
+    }
+
+    if param.resume {
+        args.push("--continue".to_string());
+    }
+
+    args.extend(runtime_args(tools, &settings.js_runtime)?);
+    args.extend(progress_template_args());
+
+    Ok(args)
+}
+
+// ダウンロードせずにJSONで情報だけを取得するときの引数
+pub(crate) fn build_probe_args(
+    url: &str,
+    probe_flags: &[&str],
+    is_cookie: bool,
+    settings: &Settings,
+    tools: &ToolPaths,
+) -> Result<Vec<String>, String> {
+    if url.trim().is_empty() {
+        return Err("URLが指定されていません".into());
+    }
+    let mut args = vec![validate_url(url)?];
+    args.extend(probe_flags.iter().map(|flag| flag.to_string()));
+    args.push("--no-warnings".to_string());
+    args.extend(cookie_args(
+        is_cookie,
+        &settings.browser,
+        &settings.cookies,
+    )?);
+    args.extend(proxy_args(&settings.proxy, url)?);
+    args.extend(runtime_args(tools, &settings.js_runtime)?);
+    Ok(args)
+}
+
+pub(crate) fn cookie_args(
+    is_cookie: bool,
+    browser: &str,
+    cookies: &CookieSettings,
+) -> Result<Vec<String>, String> {
+    if !is_cookie {
+        return Ok(Vec::new());
+    }
+    cookies.validate(browser)?;
+    if cookies.source == COOKIE_SOURCE_FILE {
+        return Ok(vec![
+            "--cookies".to_string(),
+            cookies.file_path.trim().to_string(),
+        ]);
+    }
+    Ok(vec![
+        "--cookies-from-browser".to_string(),
+        cookies.browser_spec(browser),
+    ])
+}
+
+fn network_args(network: &NetworkSettings) -> Result<Vec<String>, String> {
+    network.validate()?;
+    let mut args = vec![
+        "--retries".to_string(),
+        network.retries.to_string(),
+        "--fragment-retries".to_string(),
+        network.fragment_retries.to_string(),
+    ];
+    if !network.rate_limit.is_empty() {
+        args.push("--limit-rate".to_string());
+        args.push(network.rate_limit.clone());
+    }
+    if network.concurrent_fragments > 1 {
+        args.push("--concurrent-fragments".to_string());
+        args.push(network.concurrent_fragments.to_string());
+    }
+    if network.socket_timeout > 0 {
+        args.push("--socket-timeout".to_string());
+        args.push(network.socket_timeout.to_string());
+    }
+    if !network.throttled_rate.is_empty() {
+        args.push("--throttled-rate".to_string());
+        args.push(network.throttled_rate.clone());
+    }
+    Ok(args)
+}
+
+fn proxy_args(proxy: &ProxySettings, url: &str) -> Result<Vec<String>, String> {
+    if bypasses_proxy(proxy, url) {
+        return Ok(Vec::new());
+    }
+    Ok(match proxy_url(proxy)? {
+        Some(proxy_url) => vec![
+            "--proxy".to_string(),
+            proxy_url.as_str().trim_end_matches('/').to_string(),
+        ],
+        None => Vec::new(),
+    })
+}
+
+// 解決したdenoのパスを明示して渡し、PATH上の別のランタイムが使われないようにする
+fn runtime_args(tools: &ToolPaths, js_runtime: &JsRuntimeSettings) -> Result<Vec<String>, String> {
+    js_runtime.validate()?;
+    let mut args = ffmpeg_location_args(tools);
+    if !js_runtime.uses_deno() {
+        args.push("--no-js-runtimes".to_string());
+        return Ok(args);
+    }
+    let deno_path = tools.deno.trim();
+    if !deno_path.is_empty() {
+        args.push("--js-runtimes".to_string());
+        args.push(format!("deno:{}", deno_path));
+    }
+    match js_runtime.mode.as_str() {
+        JS_RUNTIME_REMOTE => {
+            args.push("--remote-components".to_string());
+            args.push("ejs:github".to_string());
+        }
+        JS_RUNTIME_LOCAL => {
+            // 事前に取得したコンポーネントをキャッシュとして読ませ、ネットワークから取得しない
+            args.push("--cache-dir".to_string());
+            args.push(js_runtime.component_dir.trim().to_string());
+        }
+        _ => {}
+    }
+    Ok(args)
+}
+
+// 映像と音声のマージや区間切り出し、埋め込みなどの後処理はffmpegが無いと失敗するため、起動前に判定する
+fn requires_ffmpeg(
+    kind: DownloadMode,
+    codec_id: &str,
+    modifies_output: bool,
+    audio_format: &AudioFormatSettings,
+    subtitles: &SubtitleOptions,
+) -> bool {
+    if modifies_output {
+        return true;
+    }
+    match kind {
+        DownloadMode::Normal
+        | DownloadMode::Video1080p
+        | DownloadMode::Video720p
+        | DownloadMode::Video480p
+        | DownloadMode::Video360p
+        | DownloadMode::LiveFromStart
+        | DownloadMode::LiveFromNow => true,
+        DownloadMode::CodecId => codec_id.contains('+'),
+        DownloadMode::AudioOnly => audio_format.is_conversion(),
+        DownloadMode::Subtitle => subtitles.requires_ffmpeg(),
+        DownloadMode::ListFormats | DownloadMode::Thumbnail | DownloadMode::ArbitraryCode => false,
+    }
+}
+
+// 動画や音声の本体を保存するモードか。埋め込みはこれらのモードにだけ適用する
+fn downloads_media(kind: DownloadMode) -> bool {
+    matches!(
+        kind,
+        DownloadMode::Normal
+            | DownloadMode::AudioOnly
+            | DownloadMode::Video1080p
+            | DownloadMode::Video720p
+            | DownloadMode::Video480p
+            | DownloadMode::Video360p
+            | DownloadMode::CodecId
+            | DownloadMode::LiveFromStart
+            | DownloadMode::LiveFromNow
+    )
+}
+
+// 映像を含むファイルを保存するモードか。remuxやrecodeはこれらのモードにだけ適用する
+fn converts_video(kind: DownloadMode) -> bool {
+    downloads_media(kind) && !matches!(kind, DownloadMode::AudioOnly)
+}
+
+// 変換に使うffmpegのエンコーダー。ダウンロード前に実際のffmpegが対応しているか確認する
+pub fn required_ffmpeg_encoder(
+    param: &RunCommandParam,
+    settings: &Settings,
+) -> Option<&'static str> {
+    let convert = param.convert.as_ref().unwrap_or(&settings.convert);
+    if !converts_video(param.kind) || convert.mode != CONVERT_MODE_RECODE {
+        return None;
     }
+    convert.video_encoder()
+}
 
-    if param.is_cookie {
-        args.push("--cookies-from-browser".to_string());
-        args.push(settings.browser.clone());
+fn video_codec_preset_args(encoder: &str) -> &'static str {
+    match encoder {
+        "libx264" => "-c:v libx264 -preset medium -crf 23",
+        "libx265" => "-c:v libx265 -preset medium -crf 28 -tag:v hvc1",
+        "libvpx-vp9" => "-c:v libvpx-vp9 -crf 31 -b:v 0",
+        "libsvtav1" => "-c:v libsvtav1 -preset 8 -crf 35",
+        "prores_ks" => "-c:v prores_ks -profile:v 3",
+        _ => "",
     }
+}
 
-    args.push("--remote-components".to_string());
-    args.push("ejs:github".to_string());
+fn convert_args(kind: DownloadMode, convert: &ConvertSettings) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    if !converts_video(kind) || !convert.is_active() {
+        return Ok(args);
+    }
+    let (flag, postprocessor) = if convert.mode == CONVERT_MODE_REMUX {
+        ("--remux-video", "VideoRemuxer")
+    } else {
+        ("--recode-video", "VideoConvertor")
+    };
+    args.push(flag.to_string());
+    args.push(convert.container.clone());
 
+    let mut ffmpeg_args = Vec::new();
+    if let Some(encoder) = convert
+        .video_encoder()
+        .filter(|_| convert.mode == CONVERT_MODE_RECODE)
+    {
+        ffmpeg_args.push(video_codec_preset_args(encoder).to_string());
+    }
+    let extra_args = convert.postprocessor_args.trim();
+    if !extra_args.is_empty() {
+        ffmpeg_args.push(extra_args.to_string());
+    }
+    if !ffmpeg_args.is_empty() {
+        args.push("--postprocessor-args".to_string());
+        args.push(format!("{}:{}", postprocessor, ffmpeg_args.join(" ")));
+    }
     Ok(args)
 }
 
+fn embed_args(kind: DownloadMode, embed: &EmbedSettings) -> Vec<String> {
+    let mut args = Vec::new();
+    if !downloads_media(kind) {
+        return args;
+    }
+    if embed.metadata {
+        args.push("--embed-metadata".to_string());
+    }
+    if embed.thumbnail {
+        args.push("--embed-thumbnail".to_string());
+        if !embed.thumbnail_format.is_empty() {
+            args.push("--convert-thumbnails".to_string());
+            args.push(embed.thumbnail_format.clone());
+        }
+    }
+    if embed.chapters {
+        args.push("--embed-chapters".to_string());
+    }
+    // 音声のみのファイルには字幕を埋め込めない
+    if embed.subtitles && !matches!(kind, DownloadMode::AudioOnly) {
+        args.push("--embed-subs".to_string());
+    }
+    if embed.split_chapters {
+        args.push("--split-chapters".to_string());
+    }
+    args
+}
+
+fn sponsorblock_args(kind: DownloadMode, sponsorblock: &SponsorBlockSettings) -> Vec<String> {
+    let mut args = Vec::new();
+    if !downloads_media(kind) || !sponsorblock.is_active() {
+        return args;
+    }
+    if !sponsorblock.remove.is_empty() {
+        args.push("--sponsorblock-remove".to_string());
+        args.push(sponsorblock.remove.join(","));
+    }
+    if !sponsorblock.mark.is_empty() {
+        args.push("--sponsorblock-mark".to_string());
+        args.push(sponsorblock.mark.join(","));
+    }
+    let api_url = sponsorblock.api_url.trim();
+    if !api_url.is_empty() {
+        args.push("--sponsorblock-api".to_string());
+        args.push(api_url.to_string());
+    }
+    args
+}
+
+fn ffmpeg_location_args(tools: &ToolPaths) -> Vec<String> {
+    let ffmpeg_path = tools.ffmpeg.trim();
+    if ffmpeg_path.is_empty() {
+        return Vec::new();
+    }
+    vec!["--ffmpeg-location".to_string(), ffmpeg_path.to_string()]
+}
+
+// 形式の選び方。コーデックIDはダウンロードごとの指定、site_formatはサイトごとのルールの指定
+struct FormatSelection<'a> {
+    codec_id: &'a str,
+    site_format: Option<&'a VideoFormatSettings>,
+}
+
 fn args_for_mode(
     kind: DownloadMode,
     url: &str,
     save_path: &str,
-    codec_id: &str,
-    subtitle_lang: &str,
+    format: &FormatSelection,
+    subtitles: &SubtitleOptions,
     arbitrary_code: &str,
+    settings: &Settings,
 ) -> Result<Vec<String>, String> {
     match kind {
-        DownloadMode::Normal => video_download_args(url, save_path),
-        DownloadMode::AudioOnly => Ok(vec![
-            url.to_string(),
-            "-o".to_string(),
-            save_path.to_string(),
-            "-f".to_string(),
-            "bestaudio[ext=m4a]".to_string(),
-            "--no-mtime".to_string(),
-        ]),
+        DownloadMode::Normal => video_download_args(url, save_path, format.site_format),
+        DownloadMode::AudioOnly => {
+            let mut args = vec![url.to_string(), "-o".to_string(), save_path.to_string()];
+            args.extend(audio_format_args(&settings.audio_format)?);
+            args.push("--no-mtime".to_string());
+            Ok(args)
+        }
         DownloadMode::Video1080p
         | DownloadMode::Video720p
         | DownloadMode::Video480p
-        | DownloadMode::Video360p => Ok(vec![
-            url.to_string(),
-            "-o".to_string(),
-            save_path.to_string(),
-            "-f".to_string(),
-            format_command(kind),
-            "--no-mtime".to_string(),
-        ]),
+        | DownloadMode::Video360p => {
+            let mut args = vec![url.to_string(), "-o".to_string(), save_path.to_string()];
+            args.extend(resolution_format_args(kind, &settings.video_format));
+            args.push("--no-mtime".to_string());
+            Ok(args)
+        }
         DownloadMode::ListFormats => Ok(vec![
             url.to_string(),
             "--list-formats".to_string(),
             "--skip-download".to_string(),
         ]),
         DownloadMode::CodecId => {
-            if codec_id.trim().is_empty() {
+            if format.codec_id.trim().is_empty() {
                 return Err("コーデックIDが指定されていません".into());
             }
-            Ok(vec![
+            let mut args = vec![
                 url.to_string(),
                 "-o".to_string(),
                 save_path.to_string(),
                 "-f".to_string(),
-                codec_id.to_string(),
+                format.codec_id.to_string(),
                 "--no-mtime".to_string(),
-            ])
+            ];
+            // コーデックIDを優先し、bv*+baのような指定の中での選び方だけをルールに従わせる
+            if let Some(site_format) = format.site_format {
+                args.push("-S".to_string());
+                args.push(format_sort_keys(None, site_format));
+            }
+            Ok(args)
         }
         DownloadMode::LiveFromStart => {
             let mut args = vec![
@@ -172,10 +835,10 @@ fn args_for_mode(
                 save_path.to_string(),
                 "--live-from-start".to_string(),
             ];
-            args.extend(video_format_args());
+            args.extend(video_format_args(format.site_format));
             Ok(args)
         }
-        DownloadMode::LiveFromNow => video_download_args(url, save_path),
+        DownloadMode::LiveFromNow => video_download_args(url, save_path, format.site_format),
         DownloadMode::Thumbnail => Ok(vec![
             url.to_string(),
             "-o".to_string(),
@@ -184,103 +847,680 @@ fn args_for_mode(
             "--skip-download".to_string(),
             "--no-mtime".to_string(),
         ]),
-        DownloadMode::Subtitle => {
-            if subtitle_lang.trim().is_empty() {
-                return Err("字幕言語が指定されていません".into());
-            }
-            Ok(vec![
-                url.to_string(),
-                "-o".to_string(),
-                save_path.to_string(),
-                "--write-auto-sub".to_string(),
-                "--sub-lang".to_string(),
-                subtitle_lang.to_string(),
-                "--skip-download".to_string(),
-            ])
-        }
-        DownloadMode::ArbitraryCode => {
-            if arbitrary_code.trim().is_empty() {
-                return Err("任意のコードが指定されていません".into());
-            }
-            Ok(vec![arbitrary_code.to_string()])
-        }
+        DownloadMode::Subtitle => subtitle_args(url, save_path, subtitles, format.site_format),
+        DownloadMode::ArbitraryCode => arbitrary_code_args(
+            url,
+            save_path,
+            &settings.save_dir,
+            arbitrary_code,
+            &settings.arbitrary_code_denied_flags,
+        ),
     }
 }
 
-fn video_download_args(url: &str, save_path: &str) -> Result<Vec<String>, String> {
-    let mut args = vec![url.to_string(), "-o".to_string(), save_path.to_string()];
-    args.extend(video_format_args());
-    Ok(args)
+fn mode_save_dir(kind: DownloadMode, dirs: &ModeSaveDirs) -> &str {
+    match kind {
+        DownloadMode::Normal
+        | DownloadMode::Video1080p
+        | DownloadMode::Video720p
+        | DownloadMode::Video480p
+        | DownloadMode::Video360p
+        | DownloadMode::CodecId => &dirs.video,
+        DownloadMode::AudioOnly => &dirs.audio,
+        DownloadMode::LiveFromStart | DownloadMode::LiveFromNow => &dirs.live,
+        DownloadMode::Thumbnail => &dirs.thumbnail,
+        DownloadMode::Subtitle => &dirs.subtitle,
+        DownloadMode::ArbitraryCode => &dirs.arbitrary_code,
+        DownloadMode::ListFormats => "",
+    }
 }
 
-fn video_format_args() -> Vec<String> {
-    vec![
-        "-f".to_string(),
-        "bestvideo[ext=mp4]+bestaudio[ext=m4a]/best[ext=mp4]/best".to_string(),
-        "--no-mtime".to_string(),
-    ]
+// 保存先はダウンロードごとの指定、サイトごとのルール、モードごとの既定、save_dirの順に優先する
+fn effective_download_settings(
+    param: RunCommandParam,
+    settings: &Settings,
+) -> (RunCommandParam, Settings, SiteRuleArgs) {
+    let mut settings = settings.clone();
+    let mode_dir = mode_save_dir(param.kind, &settings.mode_save_dirs).trim();
+    if !mode_dir.is_empty() {
+        settings.save_dir = mode_dir.to_string();
+    }
+    let (param, mut settings, site_rule) = apply_site_rules(param, settings);
+    if let Some(save_dir) = param
+        .save_dir
+        .as_deref()
+        .map(str::trim)
+        .filter(|save_dir| !save_dir.is_empty())
+    {
+        settings.save_dir = save_dir.to_string();
+    }
+    (param, settings, site_rule)
 }
 
-fn format_command(kind: DownloadMode) -> String {
-    let video_ids = match kind {
-        DownloadMode::Video1080p => ["616", "270", "137", "614", "248", "399"],
-        DownloadMode::Video720p => ["232", "609", "247", "136", "398", ""],
-        DownloadMode::Video480p => ["231", "606", "244", "135", "397", ""],
-        DownloadMode::Video360p => ["230", "605", "243", "134", "396", ""],
-        _ => ["", "", "", "", "", ""],
-    };
+// ファイルを保存するモードなら、実際に書き込む保存先フォルダを返す
+pub fn resolve_save_dir(param: &RunCommandParam, settings: &Settings) -> Option<String> {
+    if matches!(param.kind, DownloadMode::ListFormats) {
+        return None;
+    }
+    let (_, settings, _) = effective_download_settings(param.clone(), settings);
+    Some(settings.save_dir)
+}
 
-    video_ids
+// サイトごとのルールのうち、設定を書き換えるだけでは反映できないもの
+#[derive(Default)]
+struct SiteRuleArgs {
+    extra_args: Vec<String>,
+    // 解像度指定以外のモードでも形式を選ばせるため、ルールで指定されたかを残す
+    video_format: Option<VideoFormatSettings>,
+}
+
+// URLに一致したサイトごとのルールを順に適用する。出力テンプレートはダウンロードごとの指定を優先する
+fn apply_site_rules(
+    mut param: RunCommandParam,
+    mut settings: Settings,
+) -> (RunCommandParam, Settings, SiteRuleArgs) {
+    let mut site_rule = SiteRuleArgs::default();
+    let url = param.url.clone().unwrap_or_default();
+    for rule in matching_site_rules(&settings.site_rules.clone(), &url) {
+        let overrides = &rule.overrides;
+        if let Some(use_cookie) = overrides.use_cookie {
+            param.is_cookie = use_cookie;
+        }
+        if let Some(browser) = &overrides.browser {
+            settings.browser = browser.clone();
+        }
+        if let Some(video_format) = &overrides.video_format {
+            settings.video_format = video_format.clone();
+            site_rule.video_format = Some(video_format.clone());
+        }
+        if let Some(output_template) = &overrides.output_template {
+            if param
+                .output_name
+                .as_deref()
+                .unwrap_or_default()
+                .trim()
+                .is_empty()
+            {
+                param.output_name = Some(output_template.clone());
+            }
+        }
+        if let Some(save_dir) = &overrides.save_dir {
+            settings.save_dir = save_dir.clone();
+        }
+        if let Some(rule_args) = &overrides.extra_args {
+            site_rule.extra_args.push(rule_args.clone());
+        }
+    }
+    (param, settings, site_rule)
+}
+
+fn site_rule_extra_args(extra_args: &str, denied_flags: &[String]) -> Result<Vec<String>, String> {
+    let args = shell_words::split(extra_args)
+        .map_err(|e| format!("サイトごとのルールの追加引数を解析できません: {}", e))?;
+    if let Some(flag) = args
         .iter()
-        .filter(|id| !id.is_empty())
-        .map(|id| format!("{}+bestaudio", id))
-        .collect::<Vec<String>>()
-        .join("/")
+        .find_map(|arg| find_denied_flag(arg, denied_flags))
+    {
+        return Err(format!("{}はサイトごとのルールでは使用できません", flag));
+    }
+    Ok(args)
 }
 
-fn build_download_section(start_time: &str, end_time: &str) -> Option<String> {
-    let trimmed_start_time = start_time.trim();
-    let trimmed_end_time = end_time.trim();
-    if trimmed_start_time.is_empty() && trimmed_end_time.is_empty() {
-        return None;
+// 出力テンプレートを保存先フォルダの中に限定する。サブフォルダは使えるが、絶対パスや..で外に出ることはできない
+fn confined_output_path(save_dir: &str, output_name: &str) -> Result<String, String> {
+    let template = output_name.trim();
+    if template.is_empty() {
+        return Ok(format!("{}/%(title)s.%(ext)s", save_dir));
+    }
+    validate_relative_path(template)?;
+    if template.ends_with(['/', '\\']) {
+        return Err("出力ファイル名がフォルダで終わっています".into());
     }
+    Ok(format!("{}/{}", save_dir, template))
+}
 
-    if trimmed_start_time.is_empty() {
-        return Some(format!("*00:00:00-{}", trimmed_end_time));
+fn validate_relative_path(template: &str) -> Result<(), String> {
+    if template.chars().any(|c| c.is_control()) {
+        return Err("出力ファイル名に制御文字は使えません".into());
+    }
+    let is_absolute = template.starts_with(['/', '\\'])
+        || template.chars().nth(1) == Some(':')
+        || template.starts_with("~/")
+        || template.starts_with("~\\");
+    if is_absolute {
+        return Err(format!(
+            "出力ファイル名に絶対パスは指定できません: {}",
+            template
+        ));
     }
-    if trimmed_end_time.is_empty() {
-        return Some(format!("*{}-", trimmed_start_time));
+    // %(title&../{})sのようにフィールドの置換文字列に含めても外に出られるので、..の後の区切り文字も拒否する
+    let escapes = template.contains("../")
+        || template.contains("..\\")
+        || template
+            .split(['/', '\\'])
+            .any(|component| component.trim() == "..");
+    if escapes {
+        return Err(format!(
+            "出力ファイル名で保存先の外を指定することはできません: {}",
+            template
+        ));
     }
-    Some(format!("*{}-{}", trimmed_start_time, trimmed_end_time))
+    Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+// -o thumbnail:%(title)s.%(ext)s や -P temp:tmp のような種類の指定を分ける
+fn split_type_prefix(value: &str) -> (&str, &str) {
+    match value.split_once(':') {
+        Some((kind, rest))
+            if kind.len() > 1 && kind.chars().all(|c| c.is_ascii_lowercase() || c == '_') =>
+        {
+            (&value[..kind.len() + 1], rest)
+        }
+        _ => ("", value),
+    }
+}
 
-    fn settings() -> Settings {
-        Settings {
+// 任意コードの-oの値。種類の指定を残したまま、テンプレートを保存先の中に限定する
+fn confined_output_option(save_dir: &str, value: &str) -> Result<String, String> {
+    let (kind, template) = split_type_prefix(value.trim());
+    if template.trim().is_empty() {
+        return Err("出力ファイル名が指定されていません".into());
+    }
+    Ok(format!(
+        "{}{}",
+        kind,
+        confined_output_path(save_dir, template)?
+    ))
+}
+
+// 任意コードの-Pの値。保存先の中のサブフォルダだけを指定できる
+fn confined_paths_option(save_dir: &str, value: &str) -> Result<String, String> {
+    let (kind, path) = split_type_prefix(value.trim());
+    let path = path.trim().trim_end_matches(['/', '\\']);
+    if path.is_empty() {
+        return Ok(format!("{}{}", kind, save_dir));
+    }
+    validate_relative_path(path)?;
+    Ok(format!("{}{}/{}", kind, save_dir, path))
+}
+
+fn filename_args(kind: DownloadMode, filename: &FilenameSettings) -> Vec<String> {
+    let mut args = Vec::new();
+    if matches!(kind, DownloadMode::ListFormats) {
+        return args;
+    }
+    if filename.restrict {
+        args.push("--restrict-filenames".to_string());
+    }
+    if filename.windows_safe {
+        args.push("--windows-filenames".to_string());
+    }
+    args
+}
+
+// 任意コードを引数に分割し、URLと保存先が含まれていなければ補う
+fn arbitrary_code_args(
+    url: &str,
+    save_path: &str,
+    save_dir: &str,
+    arbitrary_code: &str,
+    denied_flags: &[String],
+) -> Result<Vec<String>, String> {
+    if arbitrary_code.trim().is_empty() {
+        return Err("任意のコードが指定されていません".into());
+    }
+    let mut args = shell_words::split(arbitrary_code)
+        .map_err(|e| format!("任意のコードを解析できません: {}", e))?;
+    if let Some(flag) = args
+        .iter()
+        .find_map(|arg| find_denied_flag(arg, denied_flags))
+    {
+        return Err(format!("{}は任意コードモードでは使用できません", flag));
+    }
+    // --より後ろはすべてURLになり、アプリが後ろに足すオプションが効かなくなる
+    if args.iter().any(|arg| arg == "--") {
+        return Err("--は任意コードモードでは使用できません".into());
+    }
+
+    let has_output = confine_arbitrary_paths(&mut args, save_dir)?;
+
+    let url = url.trim();
+    if !url.is_empty() && !args.iter().any(|arg| arg == url) {
+        args.insert(0, url.to_string());
+    }
+    if !has_output {
+        args.push("-o".to_string());
+        args.push(save_path.to_string());
+    }
+    Ok(args)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PathOption {
+    Output,
+    Paths,
+}
+
+// 値を取らない短いオプション。-xo FILE のようにまとめて書かれたときに読み飛ばす
+const BOOLEAN_SHORT_FLAGS: &str = "hUiwcxkqsvjJF46";
+
+// -xoFILE のようにまとめた短いオプションを分解し、(オプション, 続けて書かれた値)を返す
+fn split_short_options(arg: &str) -> Vec<(String, &str)> {
+    let mut options = Vec::new();
+    if !arg.starts_with('-') || arg.starts_with("--") {
+        return options;
+    }
+    for (index, c) in arg.char_indices().skip(1) {
+        let value = &arg[index + c.len_utf8()..];
+        options.push((format!("-{}", c), value));
+        if !BOOLEAN_SHORT_FLAGS.contains(c) {
+            break;
+        }
+    }
+    options
+}
+
+fn path_option(option: &str) -> Option<PathOption> {
+    match option {
+        "-o" => Some(PathOption::Output),
+        "-P" => Some(PathOption::Paths),
+        // 省略形は一意でなければyt-dlp自身がエラーにする
+        _ if option.len() > 2 && "--output".starts_with(option) => Some(PathOption::Output),
+        _ if option.len() > 2 && "--paths".starts_with(option) => Some(PathOption::Paths),
+        _ => None,
+    }
+}
+
+// 任意コードの-oと-Pの値を保存先の中に書き換える。-oが指定されていればtrueを返す
+fn confine_arbitrary_paths(args: &mut [String], save_dir: &str) -> Result<bool, String> {
+    let confine = |kind: PathOption, value: &str| match kind {
+        PathOption::Output => confined_output_option(save_dir, value),
+        PathOption::Paths => confined_paths_option(save_dir, value),
+    };
+    let mut has_output = false;
+    let mut index = 0;
+    while index < args.len() {
+        let arg = args[index].clone();
+        let (option, attached) = if arg.starts_with("--") {
+            match arg.split_once('=') {
+                Some((option, value)) => (option.to_string(), Some(value)),
+                None => (arg.clone(), None),
+            }
+        } else {
+            match split_short_options(&arg).pop() {
+                Some((option, value)) => (option, (!value.is_empty()).then_some(value)),
+                None => {
+                    index += 1;
+                    continue;
+                }
+            }
+        };
+        let Some(kind) = path_option(&option) else {
+            index += 1;
+            continue;
+        };
+        has_output |= kind == PathOption::Output;
+        match attached {
+            Some(value) => {
+                let prefix = &arg[..arg.len() - value.len()];
+                args[index] = format!("{}{}", prefix, confine(kind, value)?);
+                index += 1;
+            }
+            None => {
+                let value = args
+                    .get(index + 1)
+                    .ok_or_else(|| format!("{}に値が指定されていません", option))?;
+                args[index + 1] = confine(kind, value)?;
+                index += 2;
+            }
+        }
+    }
+    Ok(has_output)
+}
+
+// yt-dlpは長いオプションの省略形も受け付けるので、禁止オプションの先頭部分に一致するものも拒否する
+// 引数の値として書かれたオプションも拒否できるよう、空白や=より前のオプション名で判定する
+fn find_denied_flag<'a>(arg: &str, denied_flags: &'a [String]) -> Option<&'a str> {
+    let option = arg
+        .trim_start()
+        .split(|c: char| c == '=' || c.is_whitespace())
+        .next()
+        .unwrap_or_default();
+    if !option.starts_with('-') || option == "-" || option == "--" {
+        return None;
+    }
+    if !option.starts_with("--") {
+        let short_options = split_short_options(option);
+        return denied_flags
+            .iter()
+            .find(|flag| short_options.iter().any(|(option, _)| option == *flag))
+            .map(|flag| flag.as_str());
+    }
+    denied_flags
+        .iter()
+        .find(|flag| flag.starts_with(option))
+        .map(|flag| flag.as_str())
+}
+
+// 出力先やファイルの読み書きにつながるオプション。サーバーCLIでは設定に関係なく拒否する
+const ARBITRARY_CODE_PATH_FLAGS: [&str; 7] = [
+    "-o",
+    "--output",
+    "-P",
+    "--paths",
+    "-a",
+    "--batch-file",
+    "--cookies",
+];
+
+fn find_arbitrary_path_flag(arbitrary_code: &str) -> Result<Option<String>, String> {
+    let denied_flags = ARBITRARY_CODE_PATH_FLAGS
+        .iter()
+        .map(|flag| flag.to_string())
+        .collect::<Vec<_>>();
+    let args = shell_words::split(arbitrary_code)
+        .map_err(|e| format!("任意のコードを解析できません: {}", e))?;
+    Ok(args
+        .iter()
+        .find_map(|arg| find_denied_flag(arg, &denied_flags))
+        .map(str::to_string))
+}
+
+// ネットワーク越しの要求で、サーバー上の任意の場所へ読み書きさせる指定を拒否する
+pub(crate) fn validate_remote_param(param: &RunCommandParam) -> Result<(), String> {
+    if let Some(url) = param.url.as_deref().filter(|url| !url.trim().is_empty()) {
+        validate_url(url)?;
+    }
+    if param
+        .save_dir
+        .as_deref()
+        .is_some_and(|save_dir| !save_dir.trim().is_empty())
+    {
+        return Err("リモート実行では保存先を指定できません".into());
+    }
+    if param
+        .convert
+        .as_ref()
+        .is_some_and(|convert| !convert.postprocessor_args.trim().is_empty())
+    {
+        return Err("リモート実行ではffmpegの追加引数を指定できません".into());
+    }
+    // サーバー上の任意のファイルを読み書きさせないよう、Cookieはブラウザの名前付きプロファイルだけにする
+    if let Some(cookies) = param.cookies.as_ref().filter(|_| param.is_cookie) {
+        if cookies.source == COOKIE_SOURCE_FILE {
+            return Err("リモート実行ではCookieファイルを指定できません".into());
+        }
+        if cookies.profile.contains(['/', '\\']) {
+            return Err("リモート実行ではブラウザのプロファイルをパスで指定できません".into());
+        }
+    }
+    // サーバー側には録画を途中で止める仕組みがないので、上限付きの録画は受け付けない
+    if param
+        .live
+        .as_ref()
+        .is_some_and(|live| live.recording_limit().is_some())
+    {
+        return Err("リモート実行では録画の長さや終了時刻を指定できません".into());
+    }
+    if matches!(param.kind, DownloadMode::ArbitraryCode) {
+        if let Some(flag) =
+            find_arbitrary_path_flag(param.arbitrary_code.as_deref().unwrap_or_default())?
+        {
+            return Err(format!(
+                "リモート実行の任意コードでは{}を使用できません",
+                flag
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn video_download_args(
+    url: &str,
+    save_path: &str,
+    site_format: Option<&VideoFormatSettings>,
+) -> Result<Vec<String>, String> {
+    let mut args = vec![url.to_string(), "-o".to_string(), save_path.to_string()];
+    args.extend(video_format_args(site_format));
+    Ok(args)
+}
+
+fn video_format_args(site_format: Option<&VideoFormatSettings>) -> Vec<String> {
+    let mut args = match site_format {
+        Some(video_format) => site_format_args(video_format),
+        None => vec![
+            "-f".to_string(),
+            "bestvideo[ext=mp4]+bestaudio[ext=m4a]/best[ext=mp4]/best".to_string(),
+        ],
+    };
+    args.push("--no-mtime".to_string());
+    args
+}
+
+// 解像度の上限がないモードでのサイトごとのルールの形式指定。固定のitagは最も高い解像度のものを使う
+fn site_format_args(video_format: &VideoFormatSettings) -> Vec<String> {
+    if video_format.strategy == FORMAT_STRATEGY_YOUTUBE_LEGACY {
+        return vec![
+            "-f".to_string(),
+            format!(
+                "{}/bv*+ba/b",
+                youtube_legacy_format(DownloadMode::Video1080p)
+            ),
+        ];
+    }
+    let mut args = vec![
+        "-f".to_string(),
+        "bv*+ba/b".to_string(),
+        "-S".to_string(),
+        format_sort_keys(None, video_format),
+    ];
+    if !video_format.preferred_container.is_empty() {
+        args.push("--merge-output-format".to_string());
+        args.push(video_format.preferred_container.clone());
+    }
+    args
+}
+
+fn subtitle_args(
+    url: &str,
+    save_path: &str,
+    subtitles: &SubtitleOptions,
+    site_format: Option<&VideoFormatSettings>,
+) -> Result<Vec<String>, String> {
+    subtitles.validate()?;
+    let mut args = vec![url.to_string(), "-o".to_string(), save_path.to_string()];
+    if matches!(
+        subtitles.source,
+        SubtitleSource::Manual | SubtitleSource::Both
+    ) {
+        args.push("--write-subs".to_string());
+    }
+    if matches!(
+        subtitles.source,
+        SubtitleSource::Auto | SubtitleSource::Both
+    ) {
+        args.push("--write-auto-subs".to_string());
+    }
+    args.push("--sub-langs".to_string());
+    args.push(
+        subtitles
+            .languages
+            .iter()
+            .map(|lang| lang.trim())
+            .filter(|lang| !lang.is_empty())
+            .collect::<Vec<_>>()
+            .join(","),
+    );
+    if !subtitles.convert.is_empty() {
+        args.push("--convert-subs".to_string());
+        args.push(subtitles.convert.clone());
+    }
+    if subtitles.embed {
+        args.extend(video_format_args(site_format));
+        args.push("--embed-subs".to_string());
+    } else {
+        args.push("--skip-download".to_string());
+    }
+    Ok(args)
+}
+
+fn resolution_format_args(kind: DownloadMode, video_format: &VideoFormatSettings) -> Vec<String> {
+    if video_format.strategy == FORMAT_STRATEGY_YOUTUBE_LEGACY {
+        return vec!["-f".to_string(), youtube_legacy_format(kind)];
+    }
+
+    let height = match kind {
+        DownloadMode::Video1080p => 1080,
+        DownloadMode::Video720p => 720,
+        DownloadMode::Video480p => 480,
+        _ => 360,
+    };
+    let mut args = vec![
+        "-f".to_string(),
+        format!(
+            "bv*[height<={height}]+ba/b[height<={height}]/bv*+ba/b",
+            height = height
+        ),
+        "-S".to_string(),
+        format_sort_keys(Some(height), video_format),
+    ];
+    if !video_format.preferred_container.is_empty() {
+        args.push("--merge-output-format".to_string());
+        args.push(video_format.preferred_container.clone());
+    }
+    args
+}
+
+// -Sに渡す並び替えキー。heightがあればその解像度に近いものを優先する
+fn format_sort_keys(height: Option<u32>, video_format: &VideoFormatSettings) -> String {
+    let mut sort_keys = height
+        .map(|height| vec![format!("res:{}", height)])
+        .unwrap_or_default();
+    if video_format.max_fps > 0 {
+        sort_keys.push(format!("fps:{}", video_format.max_fps));
+    }
+    if !video_format.preferred_vcodec.is_empty() {
+        sort_keys.push(format!("vcodec:{}", video_format.preferred_vcodec));
+    }
+    if !video_format.preferred_container.is_empty() {
+        sort_keys.push(format!("ext:{}", video_format.preferred_container));
+    }
+    sort_keys.push(if video_format.prefer_hdr {
+        "hdr".to_string()
+    } else {
+        "hdr:sdr".to_string()
+    });
+    sort_keys.join(",")
+}
+
+fn audio_format_args(audio_format: &AudioFormatSettings) -> Result<Vec<String>, String> {
+    audio_format.validate()?;
+    if !audio_format.is_conversion() {
+        return Ok(vec!["-f".to_string(), "bestaudio[ext=m4a]".to_string()]);
+    }
+
+    let mut args = vec![
+        "-f".to_string(),
+        "ba/b".to_string(),
+        "--extract-audio".to_string(),
+        "--audio-format".to_string(),
+        audio_format.codec.clone(),
+    ];
+    if !audio_format.is_lossless() {
+        args.push("--audio-quality".to_string());
+        args.push(audio_format.quality.clone());
+    }
+    if audio_format.normalize {
+        // EBU R128に合わせたラウドネスへ変換時に揃える
+        args.push("--postprocessor-args".to_string());
+        args.push("ExtractAudio:-af loudnorm=I=-16:TP=-1.5:LRA=11".to_string());
+    }
+    Ok(args)
+}
+
+// 以前の固定itag指定。YouTube以外のサイトでは一致するフォーマットが無い
+fn youtube_legacy_format(kind: DownloadMode) -> String {
+    let video_ids = match kind {
+        DownloadMode::Video1080p => ["616", "270", "137", "614", "248", "399"],
+        DownloadMode::Video720p => ["232", "609", "247", "136", "398", ""],
+        DownloadMode::Video480p => ["231", "606", "244", "135", "397", ""],
+        DownloadMode::Video360p => ["230", "605", "243", "134", "396", ""],
+        _ => ["", "", "", "", "", ""],
+    };
+
+    video_ids
+        .iter()
+        .filter(|id| !id.is_empty())
+        .map(|id| format!("{}+bestaudio", id))
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+fn build_download_sections(
+    start_time: &str,
+    end_time: &str,
+    sections: &[TimeRange],
+    chapter_sections: &[String],
+) -> Result<Vec<String>, String> {
+    let mut download_sections = Vec::new();
+    if let Some(section) = timestamp::build_section(start_time, end_time)? {
+        download_sections.push(section);
+    }
+    for section in sections {
+        if let Some(section) = timestamp::build_section(&section.start, &section.end)? {
+            download_sections.push(section);
+        }
+    }
+    for pattern in chapter_sections {
+        let pattern = pattern.trim();
+        if pattern.is_empty() {
+            continue;
+        }
+        // *で始まる値はyt-dlpが時間の区間として扱う
+        if pattern.starts_with('*') {
+            return Err(format!(
+                "チャプターの指定は*以外で始めてください: {}",
+                pattern
+            ));
+        }
+        download_sections.push(pattern.to_string());
+    }
+    Ok(download_sections)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{
+        SiteRule, SiteRuleOverrides, ARCHIVE_SCOPE_SAVE_DIR, SITE_RULE_MATCH_REGEX,
+    };
+
+    fn settings() -> Settings {
+        Settings {
             save_dir: "C:/downloads".to_string(),
             browser: "firefox".to_string(),
             ..Settings::default()
         }
     }
 
+    fn tools() -> ToolPaths {
+        ToolPaths {
+            yt_dlp: "C:/tools/yt-dlp.exe".to_string(),
+            ffmpeg: "C:/tools/ffmpeg.exe".to_string(),
+            deno: "C:/tools/deno.exe".to_string(),
+        }
+    }
+
     #[test]
     fn builds_normal_video_args() {
         let args = build_yt_dlp_args(
             RunCommandParam {
                 url: Some("https://example.com/video".to_string()),
                 kind: DownloadMode::Normal,
-                codec_id: None,
-                subtitle_lang: None,
-                output_name: None,
-                start_time: None,
-                end_time: None,
-                is_cookie: false,
-                arbitrary_code: None,
+                ..RunCommandParam::default()
             },
             &settings(),
+            &tools(),
         )
         .unwrap();
 
@@ -298,14 +1538,10 @@ mod tests {
                 url: Some("https://example.com/video".to_string()),
                 kind: DownloadMode::CodecId,
                 codec_id: Some("".to_string()),
-                subtitle_lang: None,
-                output_name: None,
-                start_time: None,
-                end_time: None,
-                is_cookie: false,
-                arbitrary_code: None,
+                ..RunCommandParam::default()
             },
             &settings(),
+            &tools(),
         );
 
         assert_eq!(result.unwrap_err(), "コーデックIDが指定されていません");
@@ -317,15 +1553,14 @@ mod tests {
             RunCommandParam {
                 url: Some("https://example.com/video".to_string()),
                 kind: DownloadMode::AudioOnly,
-                codec_id: None,
-                subtitle_lang: None,
                 output_name: Some("audio.m4a".to_string()),
                 start_time: Some("00:01:00".to_string()),
                 end_time: Some("00:02:00".to_string()),
                 is_cookie: true,
-                arbitrary_code: None,
+                ..RunCommandParam::default()
             },
             &settings(),
+            &tools(),
         )
         .unwrap();
 
@@ -336,4 +1571,1223 @@ mod tests {
             .windows(2)
             .any(|pair| pair == ["--cookies-from-browser", "firefox"]));
     }
+
+    #[test]
+    fn passes_resolved_tool_paths() {
+        let args = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/video".to_string()),
+                kind: DownloadMode::Normal,
+                ..RunCommandParam::default()
+            },
+            &settings(),
+            &tools(),
+        )
+        .unwrap();
+
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--ffmpeg-location", "C:/tools/ffmpeg.exe"]));
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--js-runtimes", "deno:C:/tools/deno.exe"]));
+    }
+
+    #[test]
+    fn selects_js_challenge_components_by_mode() {
+        let runtime = |mode: &str| {
+            let settings = Settings {
+                js_runtime: JsRuntimeSettings {
+                    mode: mode.to_string(),
+                    component_dir: std::env::temp_dir().to_string_lossy().to_string(),
+                },
+                ..settings()
+            };
+            build_yt_dlp_args(
+                RunCommandParam {
+                    url: Some("https://example.com/video".to_string()),
+                    ..RunCommandParam::default()
+                },
+                &settings,
+                &tools(),
+            )
+            .unwrap()
+        };
+
+        let remote = runtime(JS_RUNTIME_REMOTE);
+        assert!(remote
+            .windows(2)
+            .any(|pair| pair == ["--remote-components", "ejs:github"]));
+
+        let bundled = runtime("bundled");
+        assert!(bundled.contains(&"--js-runtimes".to_string()));
+        assert!(!bundled.contains(&"--remote-components".to_string()));
+
+        let local = runtime(JS_RUNTIME_LOCAL);
+        assert!(local.contains(&"--cache-dir".to_string()));
+        assert!(!local.contains(&"--remote-components".to_string()));
+
+        let disabled = runtime("disabled");
+        assert!(disabled.contains(&"--no-js-runtimes".to_string()));
+        assert!(!disabled.contains(&"--js-runtimes".to_string()));
+    }
+
+    #[test]
+    fn rejects_merging_mode_without_ffmpeg() {
+        let tools = ToolPaths {
+            ffmpeg: "".to_string(),
+            ..tools()
+        };
+        let result = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/video".to_string()),
+                kind: DownloadMode::Video720p,
+                ..RunCommandParam::default()
+            },
+            &settings(),
+            &tools,
+        );
+
+        assert_eq!(
+            result.unwrap_err(),
+            "このモードにはffmpegが必要です。ツールをダウンロードするかパスを設定してください。"
+        );
+    }
+
+    #[test]
+    fn builds_generic_resolution_format_args() {
+        let args = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/video".to_string()),
+                kind: DownloadMode::Video720p,
+                ..RunCommandParam::default()
+            },
+            &settings(),
+            &tools(),
+        )
+        .unwrap();
+
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["-f", "bv*[height<=720]+ba/b[height<=720]/bv*+ba/b"]));
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["-S", "res:720,ext:mp4,hdr:sdr"]));
+    }
+
+    #[test]
+    fn builds_audio_extraction_args() {
+        let mut settings = settings();
+        settings.audio_format.codec = "mp3".to_string();
+        settings.audio_format.quality = "192K".to_string();
+        settings.audio_format.normalize = true;
+        let args = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/video".to_string()),
+                kind: DownloadMode::AudioOnly,
+                ..RunCommandParam::default()
+            },
+            &settings,
+            &tools(),
+        )
+        .unwrap();
+
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--audio-format", "mp3"]));
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--audio-quality", "192K"]));
+        assert!(args.windows(2).any(|pair| pair
+            == [
+                "--postprocessor-args",
+                "ExtractAudio:-af loudnorm=I=-16:TP=-1.5:LRA=11"
+            ]));
+    }
+
+    #[test]
+    fn rejects_audio_conversion_without_ffmpeg() {
+        let mut settings = settings();
+        settings.audio_format.codec = "flac".to_string();
+        let tools = ToolPaths {
+            ffmpeg: "".to_string(),
+            ..tools()
+        };
+        let result = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/video".to_string()),
+                kind: DownloadMode::AudioOnly,
+                ..RunCommandParam::default()
+            },
+            &settings,
+            &tools,
+        );
+
+        assert_eq!(
+            result.unwrap_err(),
+            "このモードにはffmpegが必要です。ツールをダウンロードするかパスを設定してください。"
+        );
+    }
+
+    #[test]
+    fn prefers_per_download_embed_options() {
+        let mut settings = settings();
+        settings.embed.metadata = true;
+        let args = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/video".to_string()),
+                kind: DownloadMode::AudioOnly,
+                embed: Some(EmbedSettings {
+                    thumbnail: true,
+                    thumbnail_format: "jpg".to_string(),
+                    chapters: true,
+                    subtitles: true,
+                    ..EmbedSettings::default()
+                }),
+                ..RunCommandParam::default()
+            },
+            &settings,
+            &tools(),
+        )
+        .unwrap();
+
+        assert!(!args.contains(&"--embed-metadata".to_string()));
+        assert!(args.contains(&"--embed-thumbnail".to_string()));
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--convert-thumbnails", "jpg"]));
+        assert!(args.contains(&"--embed-chapters".to_string()));
+        assert!(!args.contains(&"--embed-subs".to_string()));
+    }
+
+    #[test]
+    fn builds_sponsorblock_args_only_when_enabled() {
+        let param = RunCommandParam {
+            url: Some("https://example.com/video".to_string()),
+            kind: DownloadMode::Normal,
+            ..RunCommandParam::default()
+        };
+        let disabled = build_yt_dlp_args(param.clone(), &settings(), &tools()).unwrap();
+
+        let mut settings = settings();
+        settings.sponsorblock.enabled = true;
+        settings.sponsorblock.api_url = "https://sponsor.example.com".to_string();
+        let enabled = build_yt_dlp_args(param, &settings, &tools()).unwrap();
+
+        assert!(!disabled.contains(&"--sponsorblock-remove".to_string()));
+        assert!(enabled
+            .windows(2)
+            .any(|pair| pair == ["--sponsorblock-remove", "sponsor,selfpromo"]));
+        assert!(enabled
+            .windows(2)
+            .any(|pair| pair == ["--sponsorblock-mark", "intro,outro"]));
+        assert!(enabled
+            .windows(2)
+            .any(|pair| pair == ["--sponsorblock-api", "https://sponsor.example.com"]));
+    }
+
+    #[test]
+    fn splits_legacy_subtitle_lang_list() {
+        let args = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/video".to_string()),
+                kind: DownloadMode::Subtitle,
+                subtitle_lang: Some("ja, en".to_string()),
+                ..RunCommandParam::default()
+            },
+            &settings(),
+            &tools(),
+        )
+        .unwrap();
+
+        assert!(args.contains(&"--write-auto-subs".to_string()));
+        assert!(args.windows(2).any(|pair| pair == ["--sub-langs", "ja,en"]));
+        assert!(args.contains(&"--skip-download".to_string()));
+    }
+
+    #[test]
+    fn converts_and_embeds_manual_subtitles() {
+        let args = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/video".to_string()),
+                kind: DownloadMode::Subtitle,
+                subtitles: Some(SubtitleOptions {
+                    source: SubtitleSource::Manual,
+                    languages: vec!["ja".to_string()],
+                    convert: "srt".to_string(),
+                    embed: true,
+                }),
+                ..RunCommandParam::default()
+            },
+            &settings(),
+            &tools(),
+        )
+        .unwrap();
+
+        assert!(args.contains(&"--write-subs".to_string()));
+        assert!(!args.contains(&"--write-auto-subs".to_string()));
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--convert-subs", "srt"]));
+        assert!(args.contains(&"--embed-subs".to_string()));
+        assert!(!args.contains(&"--skip-download".to_string()));
+    }
+
+    #[test]
+    fn splits_arbitrary_code_and_adds_url_and_output() {
+        let args = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/video".to_string()),
+                kind: DownloadMode::ArbitraryCode,
+                arbitrary_code: Some(r#"-f "bv*+ba" --embed-chapters"#.to_string()),
+                ..RunCommandParam::default()
+            },
+            &settings(),
+            &tools(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            args[..6],
+            [
+                "https://example.com/video",
+                "-f",
+                "bv*+ba",
+                "--embed-chapters",
+                "-o",
+                "C:/downloads/%(title)s.%(ext)s"
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_denied_flags_in_arbitrary_code() {
+        for code in ["--exec 'rm -rf ~'", "--exec=calc", "--exe calc"] {
+            let result = build_yt_dlp_args(
+                RunCommandParam {
+                    kind: DownloadMode::ArbitraryCode,
+                    arbitrary_code: Some(format!("https://example.com/video {}", code)),
+                    ..RunCommandParam::default()
+                },
+                &settings(),
+                &tools(),
+            );
+
+            assert_eq!(
+                result.unwrap_err(),
+                "--execは任意コードモードでは使用できません"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_end_of_options_marker_in_arbitrary_code() {
+        let result = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/video".to_string()),
+                kind: DownloadMode::ArbitraryCode,
+                arbitrary_code: Some("-f best --".to_string()),
+                ..RunCommandParam::default()
+            },
+            &settings(),
+            &tools(),
+        );
+
+        assert_eq!(
+            result.unwrap_err(),
+            "--は任意コードモードでは使用できません"
+        );
+    }
+
+    #[test]
+    fn rejects_option_as_url() {
+        let result = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("--exec=calc".to_string()),
+                kind: DownloadMode::ArbitraryCode,
+                arbitrary_code: Some("-f best".to_string()),
+                ..RunCommandParam::default()
+            },
+            &settings(),
+            &tools(),
+        );
+
+        assert_eq!(result.unwrap_err(), "URLの形式が不正です");
+        for kind in [DownloadMode::Normal, DownloadMode::ListFormats] {
+            let param = RunCommandParam {
+                url: Some("--exec=calc".to_string()),
+                kind,
+                ..RunCommandParam::default()
+            };
+            assert!(validate_remote_param(&param).is_err());
+            assert_eq!(
+                build_yt_dlp_args(param, &settings(), &tools()).unwrap_err(),
+                "URLの形式が不正です"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_denied_flags_inside_option_values() {
+        let run = |code: &str, settings: &Settings| {
+            build_yt_dlp_args(
+                RunCommandParam {
+                    url: Some("https://example.com/video".to_string()),
+                    kind: DownloadMode::ArbitraryCode,
+                    arbitrary_code: Some(code.to_string()),
+                    ..RunCommandParam::default()
+                },
+                settings,
+                &tools(),
+            )
+        };
+        let exec_only = Settings {
+            arbitrary_code_denied_flags: vec!["--exec".to_string()],
+            ..settings()
+        };
+
+        assert_eq!(
+            run(r#"--alias x "--exec {0}" --x calc"#, &exec_only).unwrap_err(),
+            "--execは任意コードモードでは使用できません"
+        );
+        for (code, flag) in [
+            ("--alias x -v", "--alias"),
+            ("--print-to-file title /tmp/x", "--print-to-file"),
+            ("--ppa ffmpeg:-y", "--ppa"),
+            ("--postprocessor-args=x", "--postprocessor-args"),
+            ("--download-archive /tmp/a", "--download-archive"),
+            ("--cache-dir /tmp", "--cache-dir"),
+            ("--load-info /tmp/x.json", "--load-info-json"),
+        ] {
+            assert_eq!(
+                run(code, &settings()).unwrap_err(),
+                format!("{}は任意コードモードでは使用できません", flag)
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_output_and_path_flags_by_default() {
+        for code in [
+            "-o /abs/path",
+            "-P ..",
+            "-P..",
+            "-xo/tmp/x",
+            "--batch-file urls.txt",
+        ] {
+            let result = build_yt_dlp_args(
+                RunCommandParam {
+                    url: Some("https://example.com/video".to_string()),
+                    kind: DownloadMode::ArbitraryCode,
+                    arbitrary_code: Some(code.to_string()),
+                    ..RunCommandParam::default()
+                },
+                &settings(),
+                &tools(),
+            );
+
+            assert!(
+                result
+                    .unwrap_err()
+                    .ends_with("は任意コードモードでは使用できません"),
+                "{}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn confines_allowed_output_and_path_flags_to_save_dir() {
+        let settings = Settings {
+            arbitrary_code_denied_flags: vec!["--exec".to_string()],
+            ..settings()
+        };
+        let run = |code: &str| {
+            build_yt_dlp_args(
+                RunCommandParam {
+                    url: Some("https://example.com/video".to_string()),
+                    kind: DownloadMode::ArbitraryCode,
+                    arbitrary_code: Some(code.to_string()),
+                    ..RunCommandParam::default()
+                },
+                &settings,
+                &tools(),
+            )
+        };
+
+        for code in [
+            "-o /abs/path",
+            "--output=../x.%(ext)s",
+            "-P ..",
+            "-Phome:/etc",
+            "-xo/abs",
+        ] {
+            assert!(run(code).is_err(), "{}", code);
+        }
+        let args = run("-o thumbnail:%(id)s.%(ext)s -P temp:tmp -o%(title)s.%(ext)s").unwrap();
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["-o", "thumbnail:C:/downloads/%(id)s.%(ext)s"]));
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["-P", "temp:C:/downloads/tmp"]));
+        assert!(args.contains(&"-oC:/downloads/%(title)s.%(ext)s".to_string()));
+        assert_eq!(args.iter().filter(|arg| arg.starts_with("-o")).count(), 2);
+        assert_eq!(
+            validate_remote_param(&RunCommandParam {
+                kind: DownloadMode::ArbitraryCode,
+                arbitrary_code: Some("-f best --paths=x".to_string()),
+                ..RunCommandParam::default()
+            })
+            .unwrap_err(),
+            "リモート実行の任意コードでは--pathsを使用できません"
+        );
+    }
+
+    #[test]
+    fn adds_save_dir_download_archive_when_enabled() {
+        let mut settings = settings();
+        settings.download_archive.enabled = true;
+        settings.download_archive.scope = ARCHIVE_SCOPE_SAVE_DIR.to_string();
+        let param = RunCommandParam {
+            url: Some("https://example.com/playlist".to_string()),
+            kind: DownloadMode::Normal,
+            ..RunCommandParam::default()
+        };
+        let args = build_yt_dlp_args(param.clone(), &settings, &tools()).unwrap();
+        let thumbnail_args = build_yt_dlp_args(
+            RunCommandParam {
+                kind: DownloadMode::Thumbnail,
+                ..param
+            },
+            &settings,
+            &tools(),
+        )
+        .unwrap();
+
+        let archive = std::path::Path::new("C:/downloads")
+            .join(".yt-dlp-archive.txt")
+            .to_string_lossy()
+            .to_string();
+        assert!(args
+            .windows(2)
+            .any(|pair| pair[0] == "--download-archive" && pair[1] == archive));
+        assert!(!thumbnail_args.contains(&"--download-archive".to_string()));
+    }
+
+    #[test]
+    fn applies_network_settings() {
+        let mut settings = settings();
+        settings.network.rate_limit = "4.2M".to_string();
+        settings.network.concurrent_fragments = 4;
+        settings.network.socket_timeout = 30;
+        let args = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/video".to_string()),
+                ..RunCommandParam::default()
+            },
+            &settings,
+            &tools(),
+        )
+        .unwrap();
+
+        assert!(args.windows(2).any(|pair| pair == ["--retries", "10"]));
+        assert!(args.windows(2).any(|pair| pair == ["--limit-rate", "4.2M"]));
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--concurrent-fragments", "4"]));
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--socket-timeout", "30"]));
+        assert!(!args.contains(&"--throttled-rate".to_string()));
+    }
+
+    #[test]
+    fn rejects_malformed_rate_limit() {
+        let mut settings = settings();
+        settings.network.rate_limit = "fast".to_string();
+        let result = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/video".to_string()),
+                ..RunCommandParam::default()
+            },
+            &settings,
+            &tools(),
+        );
+
+        assert_eq!(result.unwrap_err(), "速度制限の指定が不正です: fast");
+    }
+
+    #[test]
+    fn passes_proxy_unless_host_is_excluded() {
+        let mut settings = settings();
+        settings.proxy.enabled = true;
+        settings.proxy.url = "http://proxy.example.com:8080".to_string();
+        settings.proxy.no_proxy = vec!["intranet.example".to_string()];
+        let param = RunCommandParam {
+            url: Some("https://example.com/video".to_string()),
+            ..RunCommandParam::default()
+        };
+        let args = build_yt_dlp_args(param.clone(), &settings, &tools()).unwrap();
+        let internal_args = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://media.intranet.example/video".to_string()),
+                ..param
+            },
+            &settings,
+            &tools(),
+        )
+        .unwrap();
+
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--proxy", "http://proxy.example.com:8080"]));
+        assert!(!internal_args.contains(&"--proxy".to_string()));
+    }
+
+    #[test]
+    fn builds_browser_cookie_spec_with_profile_and_container() {
+        let cookies = CookieSettings {
+            profile: "default-release".to_string(),
+            container: "Work".to_string(),
+            keyring: "gnomekeyring".to_string(),
+            ..CookieSettings::default()
+        };
+
+        assert_eq!(
+            cookie_args(true, "Firefox", &cookies).unwrap(),
+            vec![
+                "--cookies-from-browser",
+                "firefox+GNOMEKEYRING:default-release::Work"
+            ]
+        );
+        assert_eq!(
+            cookie_args(true, "chrome", &cookies).unwrap_err(),
+            "コンテナを指定できるのはFirefoxだけです"
+        );
+        assert_eq!(
+            cookie_args(true, "netscape", &CookieSettings::default()).unwrap_err(),
+            "cookieを読み込めないブラウザです: netscape (対応: brave, chrome, chromium, edge, firefox, opera, safari, vivaldi, whale)"
+        );
+    }
+
+    #[test]
+    fn uses_netscape_cookie_file() {
+        let path = std::env::temp_dir().join(format!(
+            "yt-dlp-gui-cookies-test-{}.txt",
+            std::process::id()
+        ));
+        std::fs::write(&path, "# Netscape HTTP Cookie File\n").unwrap();
+        let cookies = CookieSettings {
+            source: COOKIE_SOURCE_FILE.to_string(),
+            file_path: path.to_string_lossy().to_string(),
+            ..CookieSettings::default()
+        };
+
+        let args = cookie_args(true, "firefox", &cookies);
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(
+            args.unwrap(),
+            vec!["--cookies".to_string(), path.to_string_lossy().to_string()]
+        );
+        assert_eq!(
+            validate_remote_param(&RunCommandParam {
+                is_cookie: true,
+                cookies: Some(cookies),
+                ..RunCommandParam::default()
+            })
+            .unwrap_err(),
+            "リモート実行ではCookieファイルを指定できません"
+        );
+    }
+
+    #[test]
+    fn rejects_cookie_profile_path_for_remote_run() {
+        let param = |profile: &str| RunCommandParam {
+            is_cookie: true,
+            cookies: Some(CookieSettings {
+                profile: profile.to_string(),
+                ..CookieSettings::default()
+            }),
+            ..RunCommandParam::default()
+        };
+
+        assert!(validate_remote_param(&param("default-release")).is_ok());
+        for profile in ["/home/user/.mozilla/firefox/x", "..\\Profiles\\x"] {
+            assert_eq!(
+                validate_remote_param(&param(profile)).unwrap_err(),
+                "リモート実行ではブラウザのプロファイルをパスで指定できません"
+            );
+        }
+    }
+
+    #[test]
+    fn adds_multiple_sections_with_keyframe_cuts() {
+        let args = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/video".to_string()),
+                sections: vec![
+                    TimeRange {
+                        start: "90".to_string(),
+                        end: "2:00".to_string(),
+                    },
+                    TimeRange {
+                        start: "-1:00".to_string(),
+                        end: "".to_string(),
+                    },
+                ],
+                chapter_sections: vec!["^Intro$".to_string()],
+                force_keyframes_at_cuts: true,
+                ..RunCommandParam::default()
+            },
+            &settings(),
+            &tools(),
+        )
+        .unwrap();
+
+        let sections = args
+            .windows(2)
+            .filter(|pair| pair[0] == "--download-sections")
+            .map(|pair| pair[1].as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sections,
+            vec!["*00:01:30-00:02:00", "*-00:01:00-inf", "^Intro$"]
+        );
+        assert!(args.contains(&"--force-keyframes-at-cuts".to_string()));
+    }
+
+    #[test]
+    fn rejects_invalid_timestamp_before_launch() {
+        let result = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/video".to_string()),
+                start_time: Some("1:2:3:4".to_string()),
+                ..RunCommandParam::default()
+            },
+            &settings(),
+            &tools(),
+        );
+
+        assert_eq!(result.unwrap_err(), "時間の形式が不正です: 1:2:3:4");
+    }
+
+    #[test]
+    fn waits_for_scheduled_live_stream() {
+        let live = LiveRecordingOptions {
+            wait_for_video: true,
+            retry_interval_secs: 30,
+            max_duration_secs: Some(3600),
+            ..LiveRecordingOptions::default()
+        };
+        let args = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/live".to_string()),
+                kind: DownloadMode::LiveFromStart,
+                live: Some(live.clone()),
+                ..RunCommandParam::default()
+            },
+            &settings(),
+            &tools(),
+        )
+        .unwrap();
+
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--wait-for-video", "30"]));
+        assert_eq!(
+            live.recording_limit().unwrap().max_duration,
+            Some(Duration::from_secs(3600))
+        );
+        assert_eq!(
+            validate_remote_param(&RunCommandParam {
+                url: Some("https://example.com/live".to_string()),
+                kind: DownloadMode::LiveFromStart,
+                live: Some(live),
+                ..RunCommandParam::default()
+            })
+            .unwrap_err(),
+            "リモート実行では録画の長さや終了時刻を指定できません"
+        );
+    }
+
+    #[test]
+    fn rejects_recording_end_time_in_the_past() {
+        let result = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/live".to_string()),
+                kind: DownloadMode::LiveFromNow,
+                live: Some(LiveRecordingOptions {
+                    end_at: Some(1),
+                    ..LiveRecordingOptions::default()
+                }),
+                ..RunCommandParam::default()
+            },
+            &settings(),
+            &tools(),
+        );
+
+        assert_eq!(result.unwrap_err(), "終了時刻が過去になっています");
+    }
+
+    #[test]
+    fn recodes_to_target_container_with_codec_preset() {
+        let mut settings = settings();
+        settings.convert.postprocessor_args = "-c:a flac".to_string();
+        let param = RunCommandParam {
+            url: Some("https://example.com/watch".to_string()),
+            convert: Some(ConvertSettings {
+                mode: CONVERT_MODE_RECODE.to_string(),
+                container: "mkv".to_string(),
+                video_codec: "h265".to_string(),
+                postprocessor_args: "-f mp4 /tmp/copy.mp4".to_string(),
+            }),
+            ..RunCommandParam::default()
+        };
+
+        assert_eq!(required_ffmpeg_encoder(&param, &settings), Some("libx265"));
+        assert!(validate_remote_param(&param).is_err());
+        let args = build_yt_dlp_args(param, &settings, &tools()).unwrap();
+
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--recode-video", "mkv"]));
+        assert!(args.windows(2).any(|pair| pair
+            == [
+                "--postprocessor-args",
+                "VideoConvertor:-c:v libx265 -preset medium -crf 28 -tag:v hvc1 -c:a flac"
+            ]));
+    }
+
+    #[test]
+    fn remuxes_without_codec_and_skips_audio_only() {
+        let convert = ConvertSettings {
+            mode: CONVERT_MODE_REMUX.to_string(),
+            container: "mov".to_string(),
+            ..ConvertSettings::default()
+        };
+        let video_args = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/watch".to_string()),
+                convert: Some(convert.clone()),
+                ..RunCommandParam::default()
+            },
+            &settings(),
+            &tools(),
+        )
+        .unwrap();
+        let audio_args = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/watch".to_string()),
+                kind: DownloadMode::AudioOnly,
+                convert: Some(convert.clone()),
+                ..RunCommandParam::default()
+            },
+            &settings(),
+            &tools(),
+        )
+        .unwrap();
+
+        assert!(video_args
+            .windows(2)
+            .any(|pair| pair == ["--remux-video", "mov"]));
+        assert!(!audio_args.contains(&"--remux-video".to_string()));
+        assert_eq!(
+            ConvertSettings {
+                video_codec: "prores".to_string(),
+                ..convert.clone()
+            }
+            .validate()
+            .unwrap_err(),
+            "映像コーデックを指定するときは再エンコードを選んでください"
+        );
+        assert!(ConvertSettings {
+            postprocessor_args: "-r 30000/1001 -b:v 8M".to_string(),
+            ..convert.clone()
+        }
+        .validate()
+        .is_ok());
+        for (postprocessor_args, message) in [
+            ("-f mp4", "ffmpegの追加引数に-fは使えません"),
+            ("-c:a aac out.mp4", "ffmpegの追加引数にout.mp4は使えません"),
+            ("-c:a aac -i x", "ffmpegの追加引数に-iは使えません"),
+            ("-crf", "ffmpegの追加引数-crfに値がありません"),
+        ] {
+            assert_eq!(
+                ConvertSettings {
+                    postprocessor_args: postprocessor_args.to_string(),
+                    ..convert.clone()
+                }
+                .validate()
+                .unwrap_err(),
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn confines_output_template_to_save_dir() {
+        assert_eq!(
+            confined_output_path("C:/downloads", "").unwrap(),
+            "C:/downloads/%(title)s.%(ext)s"
+        );
+        assert_eq!(
+            confined_output_path("C:/downloads", "%(uploader)s/%(title)s.%(ext)s").unwrap(),
+            "C:/downloads/%(uploader)s/%(title)s.%(ext)s"
+        );
+        for template in [
+            "../%(title)s.%(ext)s",
+            "a/../../b.%(ext)s",
+            "sub\\..\\x.mp4",
+            "/etc/x",
+            "\\\\server\\share\\x",
+            "D:/x.mp4",
+            "~/x.mp4",
+        ] {
+            assert!(
+                confined_output_path("C:/downloads", template).is_err(),
+                "{}",
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn adds_filename_sanitization_flags() {
+        let settings = Settings {
+            filename: FilenameSettings {
+                restrict: true,
+                windows_safe: true,
+            },
+            ..settings()
+        };
+        let args = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/watch".to_string()),
+                ..RunCommandParam::default()
+            },
+            &settings,
+            &tools(),
+        )
+        .unwrap();
+
+        assert!(args.contains(&"--restrict-filenames".to_string()));
+        assert!(args.contains(&"--windows-filenames".to_string()));
+    }
+
+    #[test]
+    fn applies_matching_site_rules_in_order() {
+        let settings = Settings {
+            site_rules: vec![
+                SiteRule {
+                    name: "niconico".to_string(),
+                    pattern: "nicovideo.jp".to_string(),
+                    overrides: SiteRuleOverrides {
+                        use_cookie: Some(true),
+                        browser: Some("chrome".to_string()),
+                        save_dir: Some("D:/nico".to_string()),
+                        extra_args: Some("--sub-langs all".to_string()),
+                        ..SiteRuleOverrides::default()
+                    },
+                    ..SiteRule::default()
+                },
+                SiteRule {
+                    name: "live".to_string(),
+                    match_type: SITE_RULE_MATCH_REGEX.to_string(),
+                    pattern: r"/watch/lv\d+".to_string(),
+                    overrides: SiteRuleOverrides {
+                        output_template: Some("live/%(title)s.%(ext)s".to_string()),
+                        ..SiteRuleOverrides::default()
+                    },
+                    ..SiteRule::default()
+                },
+            ],
+            ..settings()
+        };
+        let args = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://live.nicovideo.jp/watch/lv123".to_string()),
+                ..RunCommandParam::default()
+            },
+            &settings,
+            &tools(),
+        )
+        .unwrap();
+
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["-o", "D:/nico/live/%(title)s.%(ext)s"]));
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--cookies-from-browser", "chrome"]));
+        assert!(args.windows(2).any(|pair| pair == ["--sub-langs", "all"]));
+        assert_eq!(
+            matching_site_rules(&settings.site_rules, "https://www.youtube.com/watch?v=a").len(),
+            0
+        );
+    }
+
+    #[test]
+    fn applies_site_rule_video_format_in_normal_mode() {
+        let settings = Settings {
+            site_rules: vec![SiteRule {
+                name: "example".to_string(),
+                pattern: "example.com".to_string(),
+                overrides: SiteRuleOverrides {
+                    video_format: Some(VideoFormatSettings {
+                        preferred_vcodec: "vp9".to_string(),
+                        preferred_container: "webm".to_string(),
+                        ..VideoFormatSettings::default()
+                    }),
+                    ..SiteRuleOverrides::default()
+                },
+                ..SiteRule::default()
+            }],
+            ..settings()
+        };
+        let run = |url: &str| {
+            build_yt_dlp_args(
+                RunCommandParam {
+                    url: Some(url.to_string()),
+                    ..RunCommandParam::default()
+                },
+                &settings,
+                &tools(),
+            )
+            .unwrap()
+        };
+
+        let args = run("https://example.com/v/1");
+        assert!(args.windows(2).any(|pair| pair == ["-f", "bv*+ba/b"]));
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["-S", "vcodec:vp9,ext:webm,hdr:sdr"]));
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--merge-output-format", "webm"]));
+        assert!(run("https://other.example.org/v/1")
+            .windows(2)
+            .any(|pair| pair
+                == [
+                    "-f",
+                    "bestvideo[ext=mp4]+bestaudio[ext=m4a]/best[ext=mp4]/best"
+                ]));
+    }
+
+    #[test]
+    fn rejects_denied_flags_in_site_rule_args() {
+        let settings = Settings {
+            site_rules: vec![SiteRule {
+                name: "example".to_string(),
+                pattern: "example.com".to_string(),
+                overrides: SiteRuleOverrides {
+                    extra_args: Some("--exec 'rm -rf /'".to_string()),
+                    ..SiteRuleOverrides::default()
+                },
+                ..SiteRule::default()
+            }],
+            ..settings()
+        };
+        let result = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/v/1".to_string()),
+                ..RunCommandParam::default()
+            },
+            &settings,
+            &tools(),
+        );
+
+        assert_eq!(
+            result.unwrap_err(),
+            "--execはサイトごとのルールでは使用できません"
+        );
+    }
+
+    #[test]
+    fn passes_extra_urls_to_single_invocation() {
+        let param = RunCommandParam {
+            url: Some("https://example.com/1".to_string()),
+            extra_urls: vec!["https://example.com/2".to_string()],
+            ..RunCommandParam::default()
+        };
+        let args = build_yt_dlp_args(param, &settings(), &tools()).unwrap();
+        let injected = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/1".to_string()),
+                extra_urls: vec!["--exec=calc".to_string()],
+                ..RunCommandParam::default()
+            },
+            &settings(),
+            &tools(),
+        );
+
+        assert_eq!(args[0], "https://example.com/1");
+        assert!(args.contains(&"https://example.com/2".to_string()));
+        assert_eq!(injected.unwrap_err(), "--exec=calc: URLの形式が不正です");
+    }
+
+    #[test]
+    fn resolves_save_dir_by_precedence() {
+        let settings = Settings {
+            mode_save_dirs: ModeSaveDirs {
+                audio: "C:/Music".to_string(),
+                ..ModeSaveDirs::default()
+            },
+            site_rules: vec![SiteRule {
+                name: "podcast".to_string(),
+                pattern: "podcast.example".to_string(),
+                overrides: SiteRuleOverrides {
+                    save_dir: Some("C:/Podcasts".to_string()),
+                    ..SiteRuleOverrides::default()
+                },
+                ..SiteRule::default()
+            }],
+            ..settings()
+        };
+        let param = |url: &str, kind: DownloadMode, save_dir: Option<&str>| RunCommandParam {
+            url: Some(url.to_string()),
+            kind,
+            save_dir: save_dir.map(str::to_string),
+            ..RunCommandParam::default()
+        };
+
+        let resolve = |param: RunCommandParam| resolve_save_dir(&param, &settings).unwrap();
+        assert_eq!(
+            resolve(param("https://a.example/1", DownloadMode::Normal, None)),
+            "C:/downloads"
+        );
+        assert_eq!(
+            resolve(param("https://a.example/1", DownloadMode::AudioOnly, None)),
+            "C:/Music"
+        );
+        assert_eq!(
+            resolve(param(
+                "https://podcast.example/1",
+                DownloadMode::AudioOnly,
+                None
+            )),
+            "C:/Podcasts"
+        );
+        assert_eq!(
+            resolve(param(
+                "https://podcast.example/1",
+                DownloadMode::AudioOnly,
+                Some("D:/once")
+            )),
+            "D:/once"
+        );
+
+        let args = build_yt_dlp_args(
+            param("https://a.example/1", DownloadMode::AudioOnly, None),
+            &settings,
+            &tools(),
+        )
+        .unwrap();
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["-o", "C:/Music/%(title)s.%(ext)s"]));
+    }
+
+    #[test]
+    fn selects_and_filters_playlist_items() {
+        let args = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://www.youtube.com/playlist?list=PL1".to_string()),
+                playlist: Some(PlaylistOptions {
+                    items: "1-5,8".to_string(),
+                    reverse: true,
+                    max_downloads: Some(3),
+                    date_after: "today-2weeks".to_string(),
+                    min_duration_secs: Some(60),
+                    max_views: Some(1000),
+                    indexed_folder: true,
+                    ..PlaylistOptions::default()
+                }),
+                ..RunCommandParam::default()
+            },
+            &settings(),
+            &tools(),
+        )
+        .unwrap();
+
+        assert!(args.contains(&"--yes-playlist".to_string()));
+        assert!(args.contains(&"--playlist-reverse".to_string()));
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--playlist-items", "1-5,8"]));
+        assert!(args.windows(2).any(|pair| pair == ["--max-downloads", "3"]));
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--dateafter", "today-2weeks"]));
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--match-filters", "duration>=60 & view_count<=1000"]));
+        assert!(args.windows(2).any(|pair| pair
+            == [
+                "-o",
+                "C:/downloads/%(playlist_title,playlist_id|playlist)s/%(playlist_index|0)03d - %(title)s.%(ext)s"
+            ]));
+    }
+
+    #[test]
+    fn rejects_invalid_playlist_options() {
+        let invalid = |playlist: PlaylistOptions| {
+            build_yt_dlp_args(
+                RunCommandParam {
+                    url: Some("https://www.youtube.com/playlist?list=PL1".to_string()),
+                    playlist: Some(playlist),
+                    ..RunCommandParam::default()
+                },
+                &settings(),
+                &tools(),
+            )
+            .unwrap_err()
+        };
+
+        assert_eq!(
+            invalid(PlaylistOptions {
+                items: "1;rm".to_string(),
+                ..PlaylistOptions::default()
+            }),
+            "再生リストの項目の指定が不正です: 1;rm"
+        );
+        assert_eq!(
+            invalid(PlaylistOptions {
+                date_before: "2024-01-01".to_string(),
+                ..PlaylistOptions::default()
+            }),
+            "日付の形式が不正です: 2024-01-01"
+        );
+        assert_eq!(
+            invalid(PlaylistOptions {
+                min_views: Some(10),
+                max_views: Some(1),
+                ..PlaylistOptions::default()
+            }),
+            "再生回数の下限が上限を超えています"
+        );
+    }
+
+    #[test]
+    fn keeps_youtube_legacy_itag_strategy() {
+        let mut settings = settings();
+        settings.video_format.strategy = FORMAT_STRATEGY_YOUTUBE_LEGACY.to_string();
+        let args = build_yt_dlp_args(
+            RunCommandParam {
+                url: Some("https://example.com/video".to_string()),
+                kind: DownloadMode::Video1080p,
+                ..RunCommandParam::default()
+            },
+            &settings,
+            &tools(),
+        )
+        .unwrap();
+
+        assert!(args.contains(
+            &"616+bestaudio/270+bestaudio/137+bestaudio/614+bestaudio/248+bestaudio/399+bestaudio"
+                .to_string()
+        ));
+        assert!(!args.contains(&"-S".to_string()));
+    }
 }
@@ -0,0 +1,265 @@
+use serde::{Deserialize, Serialize};
+
+const PROGRESS_PREFIX: &str = "[progress] ";
+const PROGRESS_FIELDS: [&str; 8] = [
+    "status",
+    "downloaded_bytes",
+    "total_bytes",
+    "total_bytes_estimate",
+    "speed",
+    "eta",
+    "fragment_index",
+    "fragment_count",
+];
+const POST_PROCESSOR_PREFIXES: [&str; 16] = [
+    "[ExtractAudio]",
+    "[Fixup",
+    "[FFmpeg",
+    "[VideoConvertor]",
+    "[VideoRemuxer]",
+    "[EmbedThumbnail]",
+    "[EmbedSubtitle]",
+    "[Metadata]",
+    "[ThumbnailsConvertor]",
+    "[SubtitlesConvertor]",
+    "[SponsorBlock]",
+    "[ModifyChapters]",
+    "[SplitChapters]",
+    "[MoveFiles]",
+    "[Exec]",
+    "[XAttrMetadata]",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadPhase {
+    Downloading,
+    Merging,
+    PostProcessing,
+    Finished,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadProgress {
+    pub phase: DownloadPhase,
+    pub percent: Option<f64>,
+    pub downloaded_bytes: Option<u64>,
+    pub total_bytes: Option<u64>,
+    pub speed: Option<f64>,
+    pub eta: Option<u64>,
+    pub fragment_index: Option<u64>,
+    pub fragment_count: Option<u64>,
+}
+
+impl DownloadProgress {
+    fn phase_only(phase: DownloadPhase) -> Self {
+        Self {
+            phase,
+            percent: None,
+            downloaded_bytes: None,
+            total_bytes: None,
+            speed: None,
+            eta: None,
+            fragment_index: None,
+            fragment_count: None,
+        }
+    }
+}
+
+pub(crate) enum ProgressLine {
+    // yt-dlpの進捗テンプレートの行。コンソールには整形した行を出す
+    Progress(DownloadProgress, String),
+    // マージや後処理の開始を示す通常の行
+    Phase(DownloadProgress),
+    Plain,
+}
+
+// 進捗を機械的に読めるよう、各値を区切り文字付きで1行ずつ出力させる
+pub(crate) fn progress_template_args() -> Vec<String> {
+    let fields = PROGRESS_FIELDS
+        .iter()
+        .map(|field| format!("%(progress.{})s", field))
+        .collect::<Vec<_>>()
+        .join("|");
+    vec![
+        "--newline".to_string(),
+        "--progress-template".to_string(),
+        format!("download:{}{}", PROGRESS_PREFIX, fields),
+    ]
+}
+
+pub(crate) fn classify_line(line: &str) -> ProgressLine {
+    if let Some(progress) = parse_progress_line(line) {
+        let formatted = format_progress_line(&progress);
+        return ProgressLine::Progress(progress, formatted);
+    }
+    match detect_phase(line) {
+        Some(phase) => ProgressLine::Phase(DownloadProgress::phase_only(phase)),
+        None => ProgressLine::Plain,
+    }
+}
+
+fn parse_progress_line(line: &str) -> Option<DownloadProgress> {
+    let values = line.trim().strip_prefix(PROGRESS_PREFIX)?;
+    let values = values.split('|').collect::<Vec<_>>();
+    if values.len() != PROGRESS_FIELDS.len() {
+        return None;
+    }
+
+    let phase = match values[0] {
+        "finished" => DownloadPhase::Finished,
+        _ => DownloadPhase::Downloading,
+    };
+    let downloaded_bytes = parse_integer(values[1]);
+    let total_bytes = parse_integer(values[2]).or_else(|| parse_integer(values[3]));
+    let percent = match phase {
+        DownloadPhase::Finished => Some(100.0),
+        _ => match (downloaded_bytes, total_bytes) {
+            (Some(downloaded), Some(total)) if total > 0 => {
+                Some((downloaded as f64 / total as f64 * 100.0).min(100.0))
+            }
+            _ => None,
+        },
+    };
+
+    Some(DownloadProgress {
+        phase,
+        percent,
+        downloaded_bytes,
+        total_bytes,
+        speed: parse_float(values[4]),
+        eta: parse_integer(values[5]),
+        fragment_index: parse_integer(values[6]),
+        fragment_count: parse_integer(values[7]),
+    })
+}
+
+fn detect_phase(line: &str) -> Option<DownloadPhase> {
+    let line = line.trim_start();
+    if line.starts_with("[Merger]") {
+        return Some(DownloadPhase::Merging);
+    }
+    if POST_PROCESSOR_PREFIXES
+        .iter()
+        .any(|prefix| line.starts_with(prefix))
+    {
+        return Some(DownloadPhase::PostProcessing);
+    }
+    None
+}
+
+// yt-dlpは値が無い場合に"NA"を出力する
+fn parse_float(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite() && *value >= 0.0)
+}
+
+fn parse_integer(value: &str) -> Option<u64> {
+    parse_float(value).map(|value| value as u64)
+}
+
+fn format_progress_line(progress: &DownloadProgress) -> String {
+    let percent = progress
+        .percent
+        .map(|percent| format!("{:5.1}%", percent))
+        .unwrap_or_else(|| "  ---%".to_string());
+    let total = progress
+        .total_bytes
+        .map(format_bytes)
+        .unwrap_or_else(|| "Unknown".to_string());
+    let mut line = format!("[download] {} of {}", percent, total);
+    if let Some(speed) = progress.speed {
+        line.push_str(&format!(" at {}/s", format_bytes(speed as u64)));
+    }
+    if let Some(eta) = progress.eta {
+        line.push_str(&format!(
+            " ETA {:02}:{:02}:{:02}",
+            eta / 3600,
+            eta % 3600 / 60,
+            eta % 60
+        ));
+    }
+    if let Some(fragment_index) = progress.fragment_index {
+        match progress.fragment_count {
+            Some(fragment_count) => {
+                line.push_str(&format!(" (frag {}/{})", fragment_index, fragment_count))
+            }
+            None => line.push_str(&format!(" (frag {})", fragment_index)),
+        }
+    }
+    line
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.2}{}", value, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_progress_template_line() {
+        let line = "[progress] downloading|5242880|10485760|NA|1048576.5|5|3|10";
+
+        let ProgressLine::Progress(progress, formatted) = classify_line(line) else {
+            panic!("進捗行として解析できる");
+        };
+
+        assert_eq!(progress.phase, DownloadPhase::Downloading);
+        assert_eq!(progress.percent, Some(50.0));
+        assert_eq!(progress.downloaded_bytes, Some(5242880));
+        assert_eq!(progress.total_bytes, Some(10485760));
+        assert_eq!(progress.eta, Some(5));
+        assert_eq!(progress.fragment_index, Some(3));
+        assert_eq!(progress.fragment_count, Some(10));
+        assert_eq!(
+            formatted,
+            "[download]  50.0% of 10.00MiB at 1.00MiB/s ETA 00:00:05 (frag 3/10)"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_estimated_total_bytes() {
+        let line = "[progress] downloading|100|NA|400|NA|NA|NA|NA";
+
+        let ProgressLine::Progress(progress, _) = classify_line(line) else {
+            panic!("進捗行として解析できる");
+        };
+
+        assert_eq!(progress.total_bytes, Some(400));
+        assert_eq!(progress.percent, Some(25.0));
+        assert_eq!(progress.speed, None);
+    }
+
+    #[test]
+    fn detects_merge_and_post_processing_phases() {
+        let ProgressLine::Phase(merge) =
+            classify_line("[Merger] Merging formats into \"video.mp4\"")
+        else {
+            panic!("マージ行として解析できる");
+        };
+        let ProgressLine::Phase(extract) = classify_line("[ExtractAudio] Destination: audio.mp3")
+        else {
+            panic!("後処理行として解析できる");
+        };
+
+        assert_eq!(merge.phase, DownloadPhase::Merging);
+        assert_eq!(extract.phase, DownloadPhase::PostProcessing);
+        assert!(matches!(
+            classify_line("[youtube] abc: Downloading webpage"),
+            ProgressLine::Plain
+        ));
+    }
+}
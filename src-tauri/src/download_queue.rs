@@ -0,0 +1,464 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Window};
+use tokio::sync::{mpsc, Mutex};
+
+use crate::{
+    command_handlers::{start_local_download, REMOTE_EXECUTION_TARGET},
+    config::{get_config_root, Settings},
+    download_command::RunCommandParam,
+    process_manager::{CommandManager, JobExit},
+};
+
+const QUEUE_FILENAME: &str = "queue.json";
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueJobStatus {
+    Pending,
+    Running,
+    Failed,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueJob {
+    pub id: u64,
+    pub param: RunCommandParam,
+    pub status: QueueJobStatus,
+    pub priority: i32,
+    pub created_at: u64,
+    pub error: Option<String>,
+    // 実行中のCommandManager側のジョブID。再起動後は意味を持たないので保存しない
+    #[serde(skip)]
+    pub running_job_id: Option<u64>,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DownloadQueue {
+    jobs: Vec<QueueJob>,
+    next_id: u64,
+    #[serde(skip)]
+    active: bool,
+    #[serde(skip)]
+    window: Option<Window>,
+}
+
+fn queue_file() -> PathBuf {
+    get_config_root().join(QUEUE_FILENAME)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+impl DownloadQueue {
+    pub fn load() -> Self {
+        let input = match fs::read_to_string(queue_file()) {
+            Ok(input) => input,
+            Err(_) => return Self::default(),
+        };
+        let mut queue: Self = serde_json::from_str(&input).unwrap_or_default();
+        queue.restore_interrupted_jobs();
+        queue
+    }
+
+    // 前回終了時に実行中だったジョブは、yt-dlpの--continueで途中から再開できるよう待機中に戻す
+    fn restore_interrupted_jobs(&mut self) {
+        for job in self
+            .jobs
+            .iter_mut()
+            .filter(|job| job.status == QueueJobStatus::Running)
+        {
+            job.status = QueueJobStatus::Pending;
+            job.param.resume = true;
+        }
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let queue_file = queue_file();
+        if let Some(parent) = queue_file.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("キューの保存先を作成できません: {}", e))?;
+        }
+        let serialized = serde_json::to_string(self)
+            .map_err(|e| format!("キューの保存に失敗しました: {}", e))?;
+        fs::write(queue_file, serialized).map_err(|e| format!("キューの保存に失敗しました: {}", e))
+    }
+
+    fn save_and_notify(&self) -> Result<(), String> {
+        self.save()?;
+        if let Some(window) = &self.window {
+            let _ = window.emit("download-queue-updated", self.jobs.clone());
+        }
+        Ok(())
+    }
+
+    pub fn jobs(&self) -> Vec<QueueJob> {
+        self.jobs.clone()
+    }
+
+    fn enqueue(&mut self, param: RunCommandParam, priority: i32) -> QueueJob {
+        self.next_id += 1;
+        let job = QueueJob {
+            id: self.next_id,
+            param,
+            status: QueueJobStatus::Pending,
+            priority,
+            created_at: now_secs(),
+            error: None,
+            running_job_id: None,
+        };
+        self.jobs.push(job.clone());
+        job
+    }
+
+    pub(crate) fn enqueue_all(
+        &mut self,
+        params: Vec<RunCommandParam>,
+        priority: i32,
+    ) -> Result<Vec<QueueJob>, String> {
+        let jobs = params
+            .into_iter()
+            .map(|param| self.enqueue(param, priority))
+            .collect();
+        self.save_and_notify()?;
+        Ok(jobs)
+    }
+
+    fn position(&self, id: u64) -> Result<usize, String> {
+        self.jobs
+            .iter()
+            .position(|job| job.id == id)
+            .ok_or_else(|| format!("キューにジョブ{}が見つかりません", id))
+    }
+
+    fn move_job(&mut self, id: u64, new_index: usize) -> Result<(), String> {
+        let index = self.position(id)?;
+        let job = self.jobs.remove(index);
+        let new_index = new_index.min(self.jobs.len());
+        self.jobs.insert(new_index, job);
+        Ok(())
+    }
+
+    fn set_priority(&mut self, id: u64, priority: i32) -> Result<(), String> {
+        let index = self.position(id)?;
+        self.jobs[index].priority = priority;
+        Ok(())
+    }
+
+    fn retry(&mut self, id: u64) -> Result<(), String> {
+        let index = self.position(id)?;
+        let job = &mut self.jobs[index];
+        if job.status != QueueJobStatus::Failed {
+            return Err("失敗したジョブのみ再試行できます".into());
+        }
+        job.status = QueueJobStatus::Pending;
+        job.error = None;
+        job.param.resume = true;
+        Ok(())
+    }
+
+    // 優先度の高い順、同じ優先度なら並び順の先頭から実行する
+    fn next_pending_index(&self) -> Option<usize> {
+        self.jobs
+            .iter()
+            .enumerate()
+            .filter(|(_, job)| job.status == QueueJobStatus::Pending)
+            .max_by(|(a_index, a), (b_index, b)| {
+                a.priority
+                    .cmp(&b.priority)
+                    .then_with(|| b_index.cmp(a_index))
+            })
+            .map(|(index, _)| index)
+    }
+
+    // 終了したジョブを反映し、空いた枠で次のジョブを始めるならtrueを返す。
+    // キュー以外のジョブが終わっても枠は空くので、どのジョブの終了でも確認する
+    fn handle_exit(&mut self, exit: &JobExit) -> Result<bool, String> {
+        let finished = self.finish_job(exit);
+        if !self.active {
+            if finished {
+                self.save_and_notify()?;
+            }
+            return Ok(false);
+        }
+        Ok(finished || self.next_pending_index().is_some())
+    }
+
+    fn finish_job(&mut self, exit: &JobExit) -> bool {
+        let Some(index) = self
+            .jobs
+            .iter()
+            .position(|job| job.running_job_id == Some(exit.job_id))
+        else {
+            return false;
+        };
+        if exit.success {
+            self.jobs.remove(index);
+            return true;
+        }
+        let job = &mut self.jobs[index];
+        job.running_job_id = None;
+        job.status = QueueJobStatus::Failed;
+        job.error = Some(if exit.stopped {
+            "停止されました".to_string()
+        } else {
+            "yt-dlpがエラーで終了しました".to_string()
+        });
+        true
+    }
+
+    async fn dispatch(
+        &mut self,
+        command_manager: &Arc<Mutex<CommandManager>>,
+        settings: &Settings,
+    ) -> Result<(), String> {
+        // ジョブの終了から呼ばれたときも、リモート実行に切り替えた後はローカルで次を始めない
+        if settings.execution_target == REMOTE_EXECUTION_TARGET {
+            self.active = false;
+        }
+        // 一時停止中でも、再試行などで変えた状態は保存して画面に伝える
+        let Some(window) = self.window.clone().filter(|_| self.active) else {
+            return self.save_and_notify();
+        };
+
+        let mut manager = command_manager.lock().await;
+        while manager.has_capacity(settings.max_concurrent_downloads as usize) {
+            let Some(index) = self.next_pending_index() else {
+                break;
+            };
+            let param = self.jobs[index].param.clone();
+            let result = start_local_download(
+                &mut manager,
+                command_manager.clone(),
+                window.clone(),
+                param,
+                settings,
+            )
+            .await;
+            let job = &mut self.jobs[index];
+            match result {
+                Ok(started) => {
+                    job.status = QueueJobStatus::Running;
+                    job.running_job_id = Some(started.job_id);
+                }
+                Err(err) => {
+                    job.status = QueueJobStatus::Failed;
+                    job.error = Some(err);
+                }
+            }
+        }
+        drop(manager);
+
+        if self.next_pending_index().is_none()
+            && !self
+                .jobs
+                .iter()
+                .any(|job| job.status == QueueJobStatus::Running)
+        {
+            self.active = false;
+        }
+        self.save_and_notify()
+    }
+}
+
+pub async fn run_queue_worker(
+    mut exit_receiver: mpsc::UnboundedReceiver<JobExit>,
+    queue: Arc<Mutex<DownloadQueue>>,
+    command_manager: Arc<Mutex<CommandManager>>,
+    settings: Arc<Mutex<Settings>>,
+) {
+    while let Some(exit) = exit_receiver.recv().await {
+        let settings = settings.lock().await.clone();
+        let mut queue = queue.lock().await;
+        let result = match queue.handle_exit(&exit) {
+            Ok(true) => queue.dispatch(&command_manager, &settings).await,
+            Ok(false) => Ok(()),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = result {
+            eprintln!("{}", err);
+        }
+    }
+}
+
+pub mod commands {
+    use super::*;
+    use crate::config::AppState;
+    use tauri::State;
+
+    #[tauri::command]
+    pub async fn get_download_queue(
+        queue: State<'_, Arc<Mutex<DownloadQueue>>>,
+    ) -> Result<Vec<QueueJob>, String> {
+        Ok(queue.lock().await.jobs())
+    }
+
+    #[tauri::command]
+    pub async fn enqueue_download(
+        queue: State<'_, Arc<Mutex<DownloadQueue>>>,
+        param: RunCommandParam,
+        priority: Option<i32>,
+    ) -> Result<QueueJob, String> {
+        let mut queue = queue.lock().await;
+        let job = queue.enqueue(param, priority.unwrap_or(0));
+        queue.save_and_notify()?;
+        Ok(job)
+    }
+
+    #[tauri::command]
+    pub async fn remove_queued_download(
+        queue: State<'_, Arc<Mutex<DownloadQueue>>>,
+        command_manager: State<'_, Arc<Mutex<CommandManager>>>,
+        window: Window,
+        id: u64,
+    ) -> Result<(), String> {
+        let mut queue = queue.lock().await;
+        let index = queue.position(id)?;
+        let job = queue.jobs.remove(index);
+        if let Some(running_job_id) = job.running_job_id {
+            CommandManager::stop_command(command_manager.inner(), Some(running_job_id), window)
+                .await?;
+        }
+        queue.save_and_notify()
+    }
+
+    #[tauri::command]
+    pub async fn move_queued_download(
+        queue: State<'_, Arc<Mutex<DownloadQueue>>>,
+        id: u64,
+        new_index: usize,
+    ) -> Result<(), String> {
+        let mut queue = queue.lock().await;
+        queue.move_job(id, new_index)?;
+        queue.save_and_notify()
+    }
+
+    #[tauri::command]
+    pub async fn set_queued_download_priority(
+        queue: State<'_, Arc<Mutex<DownloadQueue>>>,
+        id: u64,
+        priority: i32,
+    ) -> Result<(), String> {
+        let mut queue = queue.lock().await;
+        queue.set_priority(id, priority)?;
+        queue.save_and_notify()
+    }
+
+    #[tauri::command]
+    pub async fn retry_queued_download(
+        queue: State<'_, Arc<Mutex<DownloadQueue>>>,
+        command_manager: State<'_, Arc<Mutex<CommandManager>>>,
+        app_state: State<'_, AppState>,
+        id: u64,
+    ) -> Result<(), String> {
+        let settings = app_state.settings.lock().await.clone();
+        let mut queue = queue.lock().await;
+        queue.retry(id)?;
+        queue.dispatch(command_manager.inner(), &settings).await
+    }
+
+    #[tauri::command]
+    pub async fn start_download_queue(
+        queue: State<'_, Arc<Mutex<DownloadQueue>>>,
+        command_manager: State<'_, Arc<Mutex<CommandManager>>>,
+        app_state: State<'_, AppState>,
+        window: Window,
+    ) -> Result<(), String> {
+        let settings = app_state.settings.lock().await.clone();
+        if settings.execution_target == REMOTE_EXECUTION_TARGET {
+            return Err("リモート実行中はダウンロードキューを使用できません".into());
+        }
+        let mut queue = queue.lock().await;
+        queue.active = true;
+        queue.window = Some(window);
+        queue.dispatch(command_manager.inner(), &settings).await
+    }
+
+    #[tauri::command]
+    pub async fn pause_download_queue(
+        queue: State<'_, Arc<Mutex<DownloadQueue>>>,
+    ) -> Result<(), String> {
+        queue.lock().await.active = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue_with_jobs(count: u64) -> DownloadQueue {
+        let mut queue = DownloadQueue::default();
+        for index in 0..count {
+            queue.enqueue(
+                RunCommandParam {
+                    url: Some(format!("https://example.com/{}", index)),
+                    ..RunCommandParam::default()
+                },
+                0,
+            );
+        }
+        queue
+    }
+
+    #[test]
+    fn picks_highest_priority_then_queue_order() {
+        let mut queue = queue_with_jobs(3);
+        assert_eq!(queue.next_pending_index(), Some(0));
+
+        queue.set_priority(3, 10).unwrap();
+        assert_eq!(queue.next_pending_index(), Some(2));
+    }
+
+    #[test]
+    fn moves_job_to_new_position() {
+        let mut queue = queue_with_jobs(3);
+        queue.move_job(3, 0).unwrap();
+
+        let ids = queue.jobs.iter().map(|job| job.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![3, 1, 2]);
+    }
+
+    #[test]
+    fn dispatches_when_non_queue_job_frees_slot() {
+        let mut queue = queue_with_jobs(2);
+        queue.active = true;
+        queue.jobs[0].status = QueueJobStatus::Running;
+        queue.jobs[0].running_job_id = Some(1);
+        let manual_exit = JobExit {
+            job_id: 2,
+            success: true,
+            stopped: false,
+        };
+
+        assert!(queue.handle_exit(&manual_exit).unwrap());
+        assert!(queue.jobs[0].status == QueueJobStatus::Running);
+
+        queue.jobs.remove(1);
+        assert!(!queue.handle_exit(&manual_exit).unwrap());
+    }
+
+    #[test]
+    fn restores_running_jobs_as_resumable() {
+        let mut queue = queue_with_jobs(1);
+        queue.jobs[0].status = QueueJobStatus::Running;
+        let serialized = serde_json::to_string(&queue).unwrap();
+
+        let mut restored: DownloadQueue = serde_json::from_str(&serialized).unwrap();
+        restored.restore_interrupted_jobs();
+
+        assert!(restored.jobs[0].status == QueueJobStatus::Pending);
+        assert!(restored.jobs[0].param.resume);
+        assert_eq!(restored.next_id, 1);
+    }
+}
@@ -1,4 +1,13 @@
+pub mod batch_urls;
+pub mod command_preview;
 pub mod config;
+pub mod destination;
+pub mod download_archive;
 pub mod download_command;
+pub mod download_progress;
+pub mod http_client;
+pub mod media_probe;
 pub mod server_cli;
+pub mod shell_words;
+pub mod timestamp;
 pub mod tools;
@@ -1,20 +1,33 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod batch_urls;
 mod client;
 mod command_handlers;
+mod command_preview;
 mod config;
+mod destination;
+mod download_archive;
 mod download_command;
+mod download_progress;
+mod download_queue;
+mod http_client;
+mod media_probe;
 mod notification;
 mod process_manager;
 #[path = "server_cli/service.rs"]
 mod server_cli_service;
+mod shell_words;
 mod system;
+mod timestamp;
 mod tools;
 mod update;
 
 use std::sync::Arc;
 
-use command_handlers::{start_download, stop_download};
+use command_handlers::{
+    get_running_downloads, start_batch_download, start_download, stop_download,
+};
+use download_queue::DownloadQueue;
 use process_manager::CommandManager;
 use system::{
     get_current_version, get_os_type, get_sorted_directory_contents, open_directory, open_file,
@@ -28,7 +41,17 @@ use window_shadows_v2::set_shadows;
 fn main() {
     let _ = fix_path_env::fix();
     let app_state = config::AppState::new();
-    let command_manager = Arc::new(tokio::sync::Mutex::new(CommandManager::new()));
+    let (job_exit_sender, job_exit_receiver) = tokio::sync::mpsc::unbounded_channel();
+    let mut command_manager = CommandManager::new();
+    command_manager.set_exit_notifier(job_exit_sender);
+    let command_manager = Arc::new(tokio::sync::Mutex::new(command_manager));
+    let download_queue = Arc::new(tokio::sync::Mutex::new(DownloadQueue::load()));
+    tauri::async_runtime::spawn(download_queue::run_queue_worker(
+        job_exit_receiver,
+        download_queue.clone(),
+        command_manager.clone(),
+        app_state.settings.clone(),
+    ));
 
     tauri::Builder::default()
         .setup(|app| {
@@ -38,9 +61,27 @@ fn main() {
         })
         .manage(app_state)
         .manage(command_manager)
+        .manage(download_queue)
         .invoke_handler(tauri::generate_handler![
             start_download,
+            start_batch_download,
             stop_download,
+            get_running_downloads,
+            download_queue::commands::get_download_queue,
+            download_queue::commands::enqueue_download,
+            download_queue::commands::remove_queued_download,
+            download_queue::commands::move_queued_download,
+            download_queue::commands::set_queued_download_priority,
+            download_queue::commands::retry_queued_download,
+            download_queue::commands::start_download_queue,
+            download_queue::commands::pause_download_queue,
+            media_probe::probe_media_formats,
+            media_probe::list_subtitle_tracks,
+            media_probe::preflight_cookies,
+            media_probe::probe_playlist,
+            command_preview::preview_download_command,
+            download_archive::commands::get_download_archive,
+            download_archive::commands::remove_download_archive_entries,
             open_directory,
             open_url_and_exit,
             get_sorted_directory_contents,
@@ -66,6 +107,23 @@ fn main() {
             config::commands::set_remote_server_url,
             config::commands::set_remote_auth_token,
             config::commands::set_server_auth_token,
+            config::commands::set_max_concurrent_downloads,
+            config::commands::set_video_format,
+            config::commands::set_audio_format,
+            config::commands::set_embed,
+            config::commands::set_sponsorblock,
+            config::commands::set_arbitrary_code_denied_flags,
+            config::commands::set_download_archive,
+            config::commands::set_network,
+            config::commands::set_proxy,
+            config::commands::set_cookies,
+            config::commands::set_convert,
+            config::commands::set_filename,
+            config::commands::set_mode_save_dirs,
+            config::commands::set_js_runtime,
+            config::commands::set_min_free_space_mb,
+            config::commands::set_site_rules,
+            config::commands::match_site_rules,
             client::remote::test_remote_server,
             server_cli_service::register_server_cli,
             server_cli_service::unregister_server_cli,
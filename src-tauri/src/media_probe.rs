@@ -0,0 +1,377 @@
+use std::collections::HashMap;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tauri::State;
+use tokio::process::Command as TokioCommand;
+
+use crate::{
+    config::{AppState, CookieSettings, Settings},
+    download_command::build_probe_args,
+    tools::{resolve_settings_tool_paths, ToolPaths},
+};
+
+// yt-dlpの-Jの出力のうち、フォーマット選択に必要な項目だけを型付きで返す
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub struct MediaInfo {
+    pub id: String,
+    pub title: Option<String>,
+    pub duration: Option<f64>,
+    pub uploader: Option<String>,
+    pub webpage_url: Option<String>,
+    #[serde(default)]
+    pub thumbnails: Vec<MediaThumbnail>,
+    #[serde(default)]
+    pub formats: Vec<MediaFormat>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub struct MediaThumbnail {
+    pub url: String,
+    pub id: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub struct MediaFormat {
+    pub format_id: String,
+    pub ext: Option<String>,
+    pub format_note: Option<String>,
+    pub resolution: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fps: Option<f64>,
+    pub vcodec: Option<String>,
+    pub acodec: Option<String>,
+    pub tbr: Option<f64>,
+    pub vbr: Option<f64>,
+    pub abr: Option<f64>,
+    pub filesize: Option<u64>,
+    pub filesize_approx: Option<u64>,
+}
+
+// --flat-playlistの出力。各動画のページは取得しないので、項目はサイトによって欠ける
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub struct PlaylistInfo {
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub uploader: Option<String>,
+    #[serde(default)]
+    pub entries: Vec<PlaylistEntry>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub struct PlaylistEntry {
+    #[serde(default)]
+    pub index: usize, // 1から始まる並び順。--playlist-itemsの番号と一致する
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub duration: Option<f64>,
+    pub view_count: Option<u64>,
+}
+
+impl PlaylistInfo {
+    fn number_entries(mut self) -> Self {
+        for (index, entry) in self.entries.iter_mut().enumerate() {
+            entry.index = index + 1;
+        }
+        self
+    }
+}
+
+#[derive(Deserialize)]
+struct SubtitleInfo {
+    #[serde(default)]
+    subtitles: HashMap<String, Vec<SubtitleFile>>,
+    #[serde(default)]
+    automatic_captions: HashMap<String, Vec<SubtitleFile>>,
+}
+
+#[derive(Deserialize)]
+struct SubtitleFile {
+    ext: Option<String>,
+    name: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubtitleTrack {
+    pub language: String,
+    pub name: Option<String>,
+    pub formats: Vec<String>,
+    pub automatic: bool, // trueなら自動生成の字幕
+}
+
+impl SubtitleInfo {
+    // 投稿者の字幕を先に、それぞれ言語コード順に並べる
+    fn into_tracks(self) -> Vec<SubtitleTrack> {
+        let mut tracks = Vec::new();
+        for (tracks_by_language, automatic) in
+            [(self.subtitles, false), (self.automatic_captions, true)]
+        {
+            let mut group = tracks_by_language
+                .into_iter()
+                .map(|(language, files)| SubtitleTrack {
+                    language,
+                    name: files.iter().find_map(|file| file.name.clone()),
+                    formats: files.into_iter().filter_map(|file| file.ext).collect(),
+                    automatic,
+                })
+                .collect::<Vec<_>>();
+            group.sort_by(|a, b| a.language.cmp(&b.language));
+            tracks.extend(group);
+        }
+        tracks
+    }
+}
+
+impl MediaInfo {
+    // yt-dlpは映像・音声が無いことを"none"で表すので、フロントで扱いやすいようnullにそろえる
+    fn normalize(mut self) -> Self {
+        for format in self.formats.iter_mut() {
+            format.vcodec = format.vcodec.take().filter(|codec| codec != "none");
+            format.acodec = format.acodec.take().filter(|codec| codec != "none");
+        }
+        self
+    }
+}
+
+pub(crate) fn parse_yt_dlp_json<T: DeserializeOwned>(output: &str) -> Result<T, String> {
+    serde_json::from_str(output.trim()).map_err(|e| format!("yt-dlpの出力を解析できません: {}", e))
+}
+
+pub(crate) fn resolve_probe_tools(settings: &Settings) -> Result<ToolPaths, String> {
+    let tools = resolve_settings_tool_paths(settings)?;
+    if tools.yt_dlp.trim().is_empty() {
+        return Err(
+            "yt-dlpが見つかりません。ツールをダウンロードするかパスを設定してください。".into(),
+        );
+    }
+    Ok(tools)
+}
+
+pub(crate) struct CapturedOutput {
+    pub stdout: String,
+    pub stderr: String,
+}
+
+pub(crate) async fn run_yt_dlp_capture(
+    yt_dlp_path: &str,
+    args: &[String],
+) -> Result<CapturedOutput, String> {
+    let mut command = TokioCommand::new(yt_dlp_path);
+    command.args(args);
+    #[cfg(target_os = "windows")]
+    command.creation_flags(0x08000000);
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    command
+        .env("LC_ALL", "en_US.UTF-8")
+        .env("LANG", "en_US.UTF-8");
+
+    let output = command
+        .output()
+        .await
+        .map_err(|e| format!("コマンドの実行に失敗しました: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "yt-dlpがエラーで終了しました: {}",
+            stderr.trim().lines().last().unwrap_or_default()
+        ));
+    }
+    Ok(CapturedOutput {
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+    })
+}
+
+#[tauri::command]
+pub async fn probe_media_formats(
+    app_state: State<'_, AppState>,
+    url: String,
+    is_cookie: bool,
+) -> Result<MediaInfo, String> {
+    let settings = app_state.settings.lock().await.clone();
+    let tools = resolve_probe_tools(&settings)?;
+    let args = build_probe_args(&url, &["-J", "--no-playlist"], is_cookie, &settings, &tools)?;
+    let output = run_yt_dlp_capture(&tools.yt_dlp, &args).await?;
+    parse_yt_dlp_json::<MediaInfo>(&output.stdout).map(MediaInfo::normalize)
+}
+
+#[tauri::command]
+pub async fn list_subtitle_tracks(
+    app_state: State<'_, AppState>,
+    url: String,
+    is_cookie: bool,
+) -> Result<Vec<SubtitleTrack>, String> {
+    let settings = app_state.settings.lock().await.clone();
+    let tools = resolve_probe_tools(&settings)?;
+    let args = build_probe_args(&url, &["-J", "--no-playlist"], is_cookie, &settings, &tools)?;
+    let output = run_yt_dlp_capture(&tools.yt_dlp, &args).await?;
+    parse_yt_dlp_json::<SubtitleInfo>(&output.stdout).map(SubtitleInfo::into_tracks)
+}
+
+#[tauri::command]
+pub async fn probe_playlist(
+    app_state: State<'_, AppState>,
+    url: String,
+    is_cookie: bool,
+) -> Result<PlaylistInfo, String> {
+    let settings = app_state.settings.lock().await.clone();
+    let tools = resolve_probe_tools(&settings)?;
+    let args = build_probe_args(
+        &url,
+        &["-J", "--flat-playlist", "--yes-playlist"],
+        is_cookie,
+        &settings,
+        &tools,
+    )?;
+    let output = run_yt_dlp_capture(&tools.yt_dlp, &args).await?;
+    parse_yt_dlp_json::<PlaylistInfo>(&output.stdout).map(PlaylistInfo::number_entries)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CookiePreflight {
+    pub cookie_count: Option<u64>, // ブラウザから読み込んだ件数。ファイルの場合はnull
+    pub message: String,
+}
+
+// yt-dlpは「Extracted 123 cookies from firefox」のように読み込んだ件数を出力する
+fn extracted_cookie_count(output: &str) -> Option<u64> {
+    output.lines().find_map(|line| {
+        let rest = line.split("Extracted ").nth(1)?;
+        let (count, rest) = rest.split_once(' ')?;
+        if !rest.starts_with("cookies from ") {
+            return None;
+        }
+        count.parse().ok()
+    })
+}
+
+// 本番のダウンロードの前に、cookieを読み込んで動画情報を取得できるか確かめる
+#[tauri::command]
+pub async fn preflight_cookies(
+    app_state: State<'_, AppState>,
+    url: String,
+    cookies: Option<CookieSettings>,
+) -> Result<CookiePreflight, String> {
+    let mut settings = app_state.settings.lock().await.clone();
+    if let Some(cookies) = cookies {
+        settings.cookies = cookies;
+    }
+    let tools = resolve_probe_tools(&settings)?;
+    let args = build_probe_args(
+        &url,
+        &["--simulate", "--skip-download", "--no-playlist"],
+        true,
+        &settings,
+        &tools,
+    )?;
+    let output = run_yt_dlp_capture(&tools.yt_dlp, &args).await?;
+    let cookie_count =
+        extracted_cookie_count(&output.stderr).or_else(|| extracted_cookie_count(&output.stdout));
+    let message = match cookie_count {
+        Some(count) => format!("{}件のcookieを読み込み、動画情報を取得できました", count),
+        None => "cookieを読み込み、動画情報を取得できました".to_string(),
+    };
+    Ok(CookiePreflight {
+        cookie_count,
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_formats_from_dump_json() {
+        let output = r#"{
+            "id": "abc",
+            "title": "Sample",
+            "duration": 12.5,
+            "uploader": "someone",
+            "thumbnails": [{"url": "https://example.com/t.jpg", "width": 320, "height": 180}],
+            "formats": [
+                {"format_id": "140", "ext": "m4a", "vcodec": "none", "acodec": "mp4a.40.2", "abr": 129.5, "filesize": 1000},
+                {"format_id": "137", "ext": "mp4", "width": 1920, "height": 1080, "fps": 30, "vcodec": "avc1.640028", "acodec": "none", "resolution": "1920x1080"}
+            ],
+            "extractor": "youtube"
+        }"#;
+
+        let info = parse_yt_dlp_json::<MediaInfo>(output).unwrap().normalize();
+
+        assert_eq!(info.title.as_deref(), Some("Sample"));
+        assert_eq!(info.thumbnails.len(), 1);
+        assert_eq!(info.formats[0].vcodec, None);
+        assert_eq!(info.formats[0].acodec.as_deref(), Some("mp4a.40.2"));
+        assert_eq!(info.formats[1].height, Some(1080));
+        assert_eq!(info.formats[1].acodec, None);
+    }
+
+    #[test]
+    fn lists_manual_subtitles_before_automatic_captions() {
+        let output = r#"{
+            "subtitles": {
+                "ja": [{"ext": "vtt", "name": "Japanese"}, {"ext": "srv3", "name": "Japanese"}]
+            },
+            "automatic_captions": {
+                "fr": [{"ext": "vtt"}],
+                "en": [{"ext": "vtt", "name": "English"}]
+            }
+        }"#;
+
+        let tracks = parse_yt_dlp_json::<SubtitleInfo>(output)
+            .unwrap()
+            .into_tracks();
+
+        assert_eq!(tracks.len(), 3);
+        assert_eq!(tracks[0].language, "ja");
+        assert_eq!(tracks[0].formats, vec!["vtt", "srv3"]);
+        assert!(!tracks[0].automatic);
+        assert_eq!(tracks[1].language, "en");
+        assert!(tracks[1].automatic);
+        assert_eq!(tracks[2].name, None);
+    }
+
+    #[test]
+    fn numbers_flat_playlist_entries() {
+        let output = r#"{
+            "id": "PL1",
+            "title": "List",
+            "_type": "playlist",
+            "entries": [
+                {"id": "a", "title": "First", "url": "https://www.youtube.com/watch?v=a", "duration": 61.0, "view_count": 10},
+                {"id": "b", "title": null, "url": "https://www.youtube.com/watch?v=b", "duration": null}
+            ]
+        }"#;
+
+        let playlist = parse_yt_dlp_json::<PlaylistInfo>(output)
+            .unwrap()
+            .number_entries();
+
+        assert_eq!(playlist.title.as_deref(), Some("List"));
+        assert_eq!(playlist.entries[0].index, 1);
+        assert_eq!(playlist.entries[0].view_count, Some(10));
+        assert_eq!(playlist.entries[1].index, 2);
+        assert_eq!(playlist.entries[1].duration, None);
+    }
+
+    #[test]
+    fn reads_extracted_cookie_count() {
+        let output = "[debug] Extracting cookies from: \"/home/a/.mozilla\"\nExtracted 128 cookies from firefox\n";
+
+        assert_eq!(extracted_cookie_count(output), Some(128));
+        assert_eq!(
+            extracted_cookie_count("[youtube] abc: Downloading webpage"),
+            None
+        );
+    }
+}
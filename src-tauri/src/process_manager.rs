@@ -1,48 +1,234 @@
+use std::collections::HashMap;
 use std::process::Stdio;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
+use serde::Serialize;
 use tauri::{Emitter, Window};
 use tokio::io::AsyncReadExt;
 use tokio::io::BufReader as TokioBufReader;
 use tokio::process::Command as TokioCommand;
 use tokio::select;
 use tokio::sync::broadcast;
+use tokio::sync::mpsc;
 use tokio::sync::Mutex;
+use tokio::sync::Notify;
 use tokio::task::{self, JoinHandle};
 
+use crate::command_preview::{format_command_line, ShellKind};
+use crate::download_command::RecordingLimit;
+use crate::download_progress::{classify_line, DownloadProgress, ProgressLine};
+
 pub struct CommandManager {
+    jobs: HashMap<u64, RunningJob>,
+    // リモートサーバーで実行中のジョブと、サーバー側のプロセスID。同時実行数に含める
+    remote_jobs: HashMap<u64, u32>,
+    next_job_id: u64,
+    exit_notifier: Option<mpsc::UnboundedSender<JobExit>>,
+}
+
+struct RunningJob {
+    pid: u32,
     command_task: Option<JoinHandle<()>>,
-    stop_signal: Option<broadcast::Sender<()>>,
+    stop_signal: broadcast::Sender<()>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartedJob {
+    pub job_id: u64,
+    pub pid: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunningJobInfo {
+    job_id: u64,
+    pid: u32,
+}
+
+pub struct JobExit {
+    pub job_id: u64,
+    pub success: bool,
+    pub stopped: bool,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessOutputEvent {
+    pub job_id: u64,
+    pub line: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessExitEvent {
+    pub job_id: u64,
+    pub message: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessProgressEvent {
+    pub job_id: u64,
+    #[serde(flatten)]
+    pub progress: DownloadProgress,
+}
+
+pub(crate) fn emit_progress(window: &Window, job_id: u64, progress: DownloadProgress) {
+    let _ = window.emit(
+        "process-progress",
+        ProcessProgressEvent { job_id, progress },
+    );
+}
+
+// 上限に達した録画を止めるとき、yt-dlpが後処理を終えるまで待つ時間
+const RECORDING_STOP_GRACE: Duration = Duration::from_secs(30);
+
+// 進捗テンプレートの行は型付きイベントとして送り、コンソールには整形した行を出す
+fn emit_line(window: &Window, job_id: u64, line: String, started: &Notify) {
+    match classify_line(&line) {
+        ProgressLine::Progress(progress, formatted) => {
+            started.notify_one();
+            emit_progress(window, job_id, progress);
+            emit_output(window, job_id, formatted);
+        }
+        ProgressLine::Phase(progress) => {
+            emit_progress(window, job_id, progress);
+            emit_output(window, job_id, line);
+        }
+        ProgressLine::Plain => emit_output(window, job_id, line),
+    }
+}
+
+pub(crate) fn emit_output(window: &Window, job_id: u64, line: impl Into<String>) {
+    let _ = window.emit(
+        "process-output",
+        ProcessOutputEvent {
+            job_id,
+            line: line.into(),
+        },
+    );
+}
+
+pub(crate) fn emit_exit(window: &Window, job_id: u64, message: impl Into<String>) {
+    let _ = window.emit(
+        "process-exit",
+        ProcessExitEvent {
+            job_id,
+            message: message.into(),
+        },
+    );
 }
 
 impl CommandManager {
     pub fn new() -> Self {
         Self {
-            command_task: None,
-            stop_signal: None,
+            jobs: HashMap::new(),
+            remote_jobs: HashMap::new(),
+            next_job_id: 1,
+            exit_notifier: None,
+        }
+    }
+
+    pub fn set_exit_notifier(&mut self, exit_notifier: mpsc::UnboundedSender<JobExit>) {
+        self.exit_notifier = Some(exit_notifier);
+    }
+
+    pub fn has_capacity(&self, max_concurrent_jobs: usize) -> bool {
+        self.jobs.len() + self.remote_jobs.len() < max_concurrent_jobs.max(1)
+    }
+
+    fn ensure_capacity(&self, max_concurrent_jobs: usize) -> Result<(), String> {
+        if !self.has_capacity(max_concurrent_jobs) {
+            return Err(format!(
+                "同時実行数の上限({})に達しています",
+                max_concurrent_jobs.max(1)
+            ));
+        }
+        Ok(())
+    }
+
+    // サーバーに開始を依頼する前に枠を確保する。プロセスIDは開始後にset_remote_pidで設定する
+    pub fn reserve_remote_job(&mut self, max_concurrent_jobs: usize) -> Result<u64, String> {
+        self.ensure_capacity(max_concurrent_jobs)?;
+        let job_id = self.allocate_job_id();
+        self.remote_jobs.insert(job_id, 0);
+        Ok(job_id)
+    }
+
+    pub fn set_remote_pid(&mut self, job_id: u64, pid: u32) {
+        if let Some(remote_pid) = self.remote_jobs.get_mut(&job_id) {
+            *remote_pid = pid;
+        }
+    }
+
+    pub fn is_remote_job(&self, job_id: u64) -> bool {
+        self.remote_jobs.contains_key(&job_id)
+    }
+
+    // 開始できなかったジョブの枠を戻す
+    pub fn release_remote_job(&mut self, job_id: u64) {
+        self.remote_jobs.remove(&job_id);
+    }
+
+    pub fn finish_remote_job(&mut self, job_id: u64, success: bool) {
+        if self.remote_jobs.remove(&job_id).is_none() {
+            return;
+        }
+        if let Some(exit_notifier) = &self.exit_notifier {
+            let _ = exit_notifier.send(JobExit {
+                job_id,
+                success,
+                stopped: false,
+            });
         }
     }
 
+    fn allocate_job_id(&mut self) -> u64 {
+        let job_id = self.next_job_id;
+        self.next_job_id += 1;
+        job_id
+    }
+
+    pub fn running_jobs(&self) -> Vec<RunningJobInfo> {
+        let mut jobs = self
+            .jobs
+            .iter()
+            .map(|(job_id, job)| RunningJobInfo {
+                job_id: *job_id,
+                pid: job.pid,
+            })
+            .chain(self.remote_jobs.iter().map(|(job_id, pid)| RunningJobInfo {
+                job_id: *job_id,
+                pid: *pid,
+            }))
+            .collect::<Vec<_>>();
+        jobs.sort_by_key(|job| job.job_id);
+        jobs
+    }
+
     pub async fn start_command(
         &mut self,
         command_manager: Arc<Mutex<CommandManager>>,
         args: Vec<String>,
         window: tauri::Window,
         yt_dlp_path: &str,
-    ) -> Result<u32, String> {
-        if self.command_task.is_some() {
-            return Err("プロセスは既に実行中です".into());
-        }
+        max_concurrent_jobs: usize,
+        recording_limit: Option<RecordingLimit>,
+    ) -> Result<StartedJob, String> {
+        self.ensure_capacity(max_concurrent_jobs)?;
 
+        let job_id = self.allocate_job_id();
         let (tx, _) = broadcast::channel(1);
-        self.stop_signal = Some(tx.clone());
 
         #[cfg(target_os = "windows")]
         let mut child = TokioCommand::new(yt_dlp_path)
             .args(&args)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .creation_flags(0x08000000)
+            // CREATE_NO_WINDOW | CREATE_NEW_PROCESS_GROUP。録画の停止でCtrl+Breakを送れるよう別のグループにする
+            .creation_flags(0x08000000 | 0x00000200)
             .spawn()
             .map_err(|e| format!("コマンドの実行に失敗しました: {}", e))?;
 
@@ -58,16 +244,15 @@ impl CommandManager {
 
         let pid = child.id().ok_or("プロセスIDの取得に失敗しました")?;
 
-        window
-            .emit(
-                "process-output",
-                format!(
-                    "{}>yt-dlp {}\n",
-                    std::env::current_dir().unwrap().to_string_lossy(),
-                    args.join(" ")
-                ),
-            )
-            .unwrap();
+        emit_output(
+            &window,
+            job_id,
+            format!(
+                "{}>{}\n",
+                std::env::current_dir().unwrap().to_string_lossy(),
+                format_command_line("yt-dlp", &args, ShellKind::native())
+            ),
+        );
 
         let stdout = child.stdout.take().ok_or("標準出力の取得に失敗しました")?;
         let stderr = child
@@ -79,26 +264,55 @@ impl CommandManager {
         let window_clone2 = window.clone();
         let tx_clone = tx.clone();
         let command_manager_clone = Arc::clone(&command_manager);
+        let exit_notifier = self.exit_notifier.clone();
+        let notify_exit = move |success: bool, stopped: bool| {
+            if let Some(exit_notifier) = &exit_notifier {
+                let _ = exit_notifier.send(JobExit {
+                    job_id,
+                    success,
+                    stopped,
+                });
+            }
+        };
 
         let task_handle = task::spawn(async move {
             let stdout_reader = TokioBufReader::new(stdout);
             let stderr_reader = TokioBufReader::new(stderr);
 
-            let stop_rx1 = tx.subscribe();
-            let stop_rx2 = tx.subscribe();
+            let stop_rx1 = tx_clone.subscribe();
+            let stop_rx2 = tx_clone.subscribe();
 
             let window_clone_stdout = window_clone.clone();
             let window_clone_stderr = window_clone2.clone();
+            // 配信待ちの間は進捗が出ないため、最初の進捗を録画開始とみなす
+            let started = Arc::new(Notify::new());
+            let started_stdout = Arc::clone(&started);
+            let started_stderr = Arc::clone(&started);
 
             let stdout_task = tokio::spawn(async move {
-                process_lines(stdout_reader, window_clone_stdout, stop_rx1).await;
+                process_lines(
+                    stdout_reader,
+                    window_clone_stdout,
+                    job_id,
+                    stop_rx1,
+                    started_stdout,
+                )
+                .await;
             });
 
             let stderr_task = tokio::spawn(async move {
-                process_lines(stderr_reader, window_clone_stderr, stop_rx2).await;
+                process_lines(
+                    stderr_reader,
+                    window_clone_stderr,
+                    job_id,
+                    stop_rx2,
+                    started_stderr,
+                )
+                .await;
             });
 
             let mut rx = tx_clone.subscribe();
+            let mut exit_success = false;
 
             tokio::select! {
                 _ = rx.recv() => {
@@ -106,57 +320,209 @@ impl CommandManager {
                         eprintln!("Failed to kill process: {}", e);
                     }
                     let _ = child.wait().await;
-                    window_clone2.emit("process-exit", "プロセス終了").unwrap();
+                    emit_exit(&window_clone2, job_id, "プロセス終了");
+                    notify_exit(false, true);
 
                     return;
                 }
                 status = child.wait() => {
                     match status {
-                        Ok(_) => {
-                            window_clone.emit("process-output", "\n").unwrap();
-                            window_clone2.emit("process-exit", "プロセス終了").unwrap();
+                        Ok(status) => {
+                            emit_output(&window_clone, job_id, "\n");
+                            emit_exit(&window_clone2, job_id, "プロセス終了");
+                            exit_success = status.success();
                         }
                         Err(e) => {
-                            window_clone
-                                .emit("process-exit", format!("プロセス終了エラー: {}", e))
-                                .unwrap();
+                            emit_exit(&window_clone, job_id, format!("プロセス終了エラー: {}", e));
                         }
                     }
                 }
+                _ = wait_for_recording_limit(recording_limit, &started) => {
+                    emit_output(&window_clone, job_id, "録画の上限に達したため停止します\n");
+                    // 停止を待つ間にユーザーが止めたときは、猶予を待たずにすぐ終了させる
+                    let stopped = tokio::select! {
+                        success = interrupt_child(&mut child, pid) => {
+                            // 強制終了したときは録画が保存されていない可能性があるので失敗とする
+                            exit_success = success;
+                            false
+                        }
+                        _ = rx.recv() => true,
+                    };
+                    if stopped {
+                        if let Err(e) = child.kill().await {
+                            eprintln!("Failed to kill process: {}", e);
+                        }
+                        let _ = child.wait().await;
+                        emit_exit(&window_clone2, job_id, "プロセス終了");
+                        notify_exit(false, true);
+
+                        return;
+                    }
+                    if !exit_success {
+                        emit_output(
+                            &window_clone,
+                            job_id,
+                            "録画を正常に停止できなかったため強制終了しました\n",
+                        );
+                    }
+                    emit_exit(&window_clone2, job_id, "プロセス終了");
+                }
             }
 
             let _ = stdout_task.await;
             let _ = stderr_task.await;
 
             let mut manager = command_manager_clone.lock().await;
-            manager.command_task = None;
+            manager.jobs.remove(&job_id);
+            drop(manager);
+            notify_exit(exit_success, false);
         });
 
-        self.command_task = Some(task_handle);
+        self.jobs.insert(
+            job_id,
+            RunningJob {
+                pid,
+                command_task: Some(task_handle),
+                stop_signal: tx,
+            },
+        );
 
-        Ok(pid)
+        Ok(StartedJob { job_id, pid })
     }
 
-    pub async fn stop_command(&mut self, window: tauri::Window) -> Result<(), String> {
-        if let Some(stop_signal) = self.stop_signal.take() {
-            if let Err(err) = stop_signal.send(()) {
+    // ジョブのタスクは終了時にCommandManagerをロックするので、ロックを外してから終了を待つ
+    pub async fn stop_command(
+        command_manager: &Arc<Mutex<CommandManager>>,
+        job_id: Option<u64>,
+        window: tauri::Window,
+    ) -> Result<(), String> {
+        let stopped = command_manager.lock().await.send_stop_signals(job_id)?;
+        for (job_id, job) in stopped {
+            if let Some(handle) = job.command_task {
+                if let Err(err) = handle.await {
+                    return Err(format!("Failed to stop command task: {}", err));
+                }
+            }
+            emit_output(&window, job_id, "プロセスを停止しました\n");
+        }
+        Ok(())
+    }
+
+    // 停止の合図を送れたジョブだけを管理から外して返す
+    fn send_stop_signals(&mut self, job_id: Option<u64>) -> Result<Vec<(u64, RunningJob)>, String> {
+        let job_ids = match job_id {
+            Some(job_id) => {
+                if !self.jobs.contains_key(&job_id) {
+                    return Err(format!("ジョブ{}は実行されていません", job_id));
+                }
+                vec![job_id]
+            }
+            None => {
+                if self.jobs.is_empty() {
+                    return Err("Command is not running.".to_string());
+                }
+                self.jobs.keys().copied().collect()
+            }
+        };
+
+        let mut stopped = Vec::new();
+        for job_id in job_ids {
+            let Some(job) = self.jobs.get(&job_id) else {
+                continue;
+            };
+            if let Err(err) = job.stop_signal.send(()) {
                 return Err(format!("Failed to send stop signal: {}", err));
             }
-        } else {
-            return Err("Command is not running.".to_string());
+            if let Some(job) = self.jobs.remove(&job_id) {
+                stopped.push((job_id, job));
+            }
         }
+        Ok(stopped)
+    }
+}
 
-        if let Some(handle) = self.command_task.take() {
-            if let Err(err) = handle.await {
-                return Err(format!("Failed to stop command task: {}", err));
+// 終了時刻か、録画開始からの上限時間のどちらか早い方まで待つ
+async fn wait_for_recording_limit(limit: Option<RecordingLimit>, started: &Notify) {
+    let Some(limit) = limit else {
+        return std::future::pending().await;
+    };
+    let until_end = async {
+        match limit.end_at {
+            Some(end_at) => {
+                let remaining = end_at
+                    .duration_since(SystemTime::now())
+                    .unwrap_or(Duration::ZERO);
+                tokio::time::sleep(remaining).await;
             }
+            None => std::future::pending().await,
         }
+    };
+    let until_max_duration = async {
+        match limit.max_duration {
+            Some(max_duration) => {
+                started.notified().await;
+                tokio::time::sleep(max_duration).await;
+            }
+            None => std::future::pending().await,
+        }
+    };
+    select! {
+        _ = until_end => {}
+        _ = until_max_duration => {}
+    }
+}
 
-        window
-            .emit("process-output", "プロセスを停止しました\n")
-            .unwrap();
-        Ok(())
+// 録画済みの部分を保存させるため、割り込みを送ってから終了を待つ。時間内に終わればtrueを返す
+async fn interrupt_child(child: &mut tokio::process::Child, pid: u32) -> bool {
+    if send_interrupt(pid).await
+        && tokio::time::timeout(RECORDING_STOP_GRACE, child.wait())
+            .await
+            .is_ok()
+    {
+        return true;
     }
+
+    if let Err(e) = child.kill().await {
+        eprintln!("Failed to kill process: {}", e);
+    }
+    let _ = child.wait().await;
+    false
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+async fn send_interrupt(pid: u32) -> bool {
+    TokioCommand::new("kill")
+        .args(["-INT", &pid.to_string()])
+        .status()
+        .await
+        .is_ok_and(|status| status.success())
+}
+
+// GUIのプロセスにはコンソールがないので、yt-dlpのコンソールに接続してからそのプロセスグループにCtrl+Breakを送る
+#[cfg(target_os = "windows")]
+async fn send_interrupt(pid: u32) -> bool {
+    use windows_sys::Win32::System::Console::{
+        AttachConsole, FreeConsole, GenerateConsoleCtrlEvent, CTRL_BREAK_EVENT,
+    };
+
+    // 接続できるコンソールはプロセス全体で1つなので、同時に停止するジョブとは順番に処理する
+    static CONSOLE_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    task::spawn_blocking(move || {
+        let _guard = CONSOLE_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        unsafe {
+            FreeConsole();
+            if AttachConsole(pid) == 0 {
+                return false;
+            }
+            let sent = GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, pid) != 0;
+            FreeConsole();
+            sent
+        }
+    })
+    .await
+    .unwrap_or(false)
 }
 
 fn decode_buffer(buffer: &[u8]) -> String {
@@ -188,7 +554,13 @@ fn decode_buffer(buffer: &[u8]) -> String {
     }
 }
 
-async fn process_lines<R>(mut reader: R, window: Window, mut stop_rx: broadcast::Receiver<()>) -> ()
+async fn process_lines<R>(
+    mut reader: R,
+    window: Window,
+    job_id: u64,
+    mut stop_rx: broadcast::Receiver<()>,
+    started: Arc<Notify>,
+) -> ()
 where
     R: AsyncReadExt + Unpin,
 {
@@ -205,13 +577,13 @@ where
                         for &byte in &temp_buffer[..n] {
                             if byte == b'\r' || byte == b'\n' {
                                 let line = decode_buffer(&buffer);
-                                window.emit("process-output", line).unwrap();
+                                emit_line(&window, job_id, line, &started);
                                 buffer.clear();
                             } else {
                                 buffer.push(byte);
                                 if buffer.len() > MAX_LINE_LENGTH {
                                     let line = decode_buffer(&buffer);
-                                    window.emit("process-output", line).unwrap();
+                                    emit_line(&window, job_id, line, &started);
                                     buffer.clear();
                                 }
                             }
@@ -231,6 +603,28 @@ where
 
     if !buffer.is_empty() {
         let line = decode_buffer(&buffer);
-        window.emit("process-output", line).unwrap();
+        emit_line(&window, job_id, line, &started);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_remote_jobs_toward_capacity() {
+        let mut manager = CommandManager::new();
+        let job_id = manager.reserve_remote_job(1).unwrap();
+        manager.set_remote_pid(job_id, 42);
+
+        assert_eq!(
+            manager.reserve_remote_job(1).unwrap_err(),
+            "同時実行数の上限(1)に達しています"
+        );
+        assert_eq!(manager.running_jobs()[0].pid, 42);
+
+        manager.finish_remote_job(job_id, true);
+        assert!(manager.has_capacity(1));
+        assert!(!manager.is_remote_job(job_id));
     }
 }
@@ -4,13 +4,24 @@ use tokio::{
     net::TcpStream,
 };
 
-use crate::{config::Settings, download_command::RunCommandParam};
+use crate::{
+    config::Settings, download_command::RunCommandParam, download_progress::DownloadProgress,
+};
 
 use super::process::SharedDownloadProcess;
 
 #[derive(Deserialize)]
 struct RunRequest {
     param: RunCommandParam,
+    // クライアント側のジョブID。古いクライアントは送らない
+    #[serde(default)]
+    job_id: Option<u64>,
+}
+
+#[derive(Default, Deserialize)]
+struct StopRequest {
+    #[serde(default)]
+    job_id: Option<u64>,
 }
 
 #[derive(Serialize)]
@@ -22,7 +33,11 @@ struct RunResponse {
 #[serde(rename_all = "camelCase")]
 struct OutputResponse {
     running: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    job_id: Option<u64>,
     outputs: Vec<OutputLine>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    progress: Option<DownloadProgress>,
 }
 
 #[derive(Serialize)]
@@ -85,6 +100,7 @@ async fn handle_http_request(
             let snapshot = download_process.snapshot_since(since).await;
             let body = serde_json::to_string(&OutputResponse {
                 running: snapshot.running,
+                job_id: snapshot.job_id,
                 outputs: snapshot
                     .outputs
                     .into_iter()
@@ -93,6 +109,7 @@ async fn handle_http_request(
                         line: output.line,
                     })
                     .collect(),
+                progress: snapshot.progress,
             })
             .map_err(|e| format!("レスポンスの作成に失敗しました: {}", e))?;
             Ok(json_response(200, "OK", body))
@@ -108,7 +125,10 @@ async fn handle_http_request(
                     ));
                 }
             };
-            let pid = match download_process.start(run_request.param).await {
+            let pid = match download_process
+                .start(run_request.param, run_request.job_id)
+                .await
+            {
                 Ok(pid) => pid,
                 Err(err) => return Ok(text_response(400, "Bad Request", &err)),
             };
@@ -117,7 +137,22 @@ async fn handle_http_request(
             Ok(json_response(200, "OK", body))
         }
         ("POST", "/stop") => {
-            if let Err(err) = download_process.stop().await {
+            // 本文がなければ、ジョブに関係なく実行中のプロセスを止める
+            let stop_request = if request.body.trim().is_empty() {
+                StopRequest::default()
+            } else {
+                match serde_json::from_str::<StopRequest>(&request.body) {
+                    Ok(stop_request) => stop_request,
+                    Err(err) => {
+                        return Ok(text_response(
+                            400,
+                            "Bad Request",
+                            &format!("リクエストの解析に失敗しました: {}", err),
+                        ));
+                    }
+                }
+            };
+            if let Err(err) = download_process.stop(stop_request.job_id).await {
                 return Ok(text_response(400, "Bad Request", &err));
             }
             Ok(text_response(200, "OK", "stopped"))
@@ -365,6 +400,18 @@ mod tests {
         assert_eq!(response.body, "プロセスは実行されていません");
     }
 
+    #[tokio::test]
+    async fn stop_with_other_job_id_returns_bad_request() {
+        let response = response_for(
+            request("POST", "/stop", Some("abc123"), "{\"job_id\":3}"),
+            "abc123",
+        )
+        .await;
+
+        assert_eq!(response.status, 400);
+        assert_eq!(response.body, "ジョブ3は実行されていません");
+    }
+
     #[tokio::test]
     async fn malformed_run_request_returns_bad_request() {
         let response = response_for(request("POST", "/run", Some("abc123"), "{"), "abc123").await;
@@ -11,8 +11,13 @@ use tokio::{
 
 use crate::{
     config::Settings,
-    download_command::{build_yt_dlp_args, RunCommandParam},
-    tools::resolve_tool_paths,
+    destination::validate_destination,
+    download_command::{
+        build_yt_dlp_args, required_ffmpeg_encoder, resolve_save_dir, validate_remote_param,
+        RunCommandParam,
+    },
+    download_progress::{classify_line, DownloadProgress, ProgressLine},
+    tools::{ensure_ffmpeg_encoder, resolve_settings_tool_paths},
 };
 
 #[derive(Clone)]
@@ -25,6 +30,8 @@ struct DownloadProcess {
     outputs: Vec<ProcessOutput>,
     next_output_id: u64,
     running: bool,
+    job_id: Option<u64>,
+    progress: Option<DownloadProgress>,
 }
 
 #[derive(Clone)]
@@ -35,7 +42,9 @@ pub(super) struct ProcessOutput {
 
 pub(super) struct ProcessSnapshot {
     pub(super) running: bool,
+    pub(super) job_id: Option<u64>,
     pub(super) outputs: Vec<ProcessOutput>,
+    pub(super) progress: Option<DownloadProgress>,
 }
 
 impl SharedDownloadProcess {
@@ -46,11 +55,17 @@ impl SharedDownloadProcess {
                 outputs: Vec::new(),
                 next_output_id: 0,
                 running: false,
+                job_id: None,
+                progress: None,
             })),
         }
     }
 
-    pub(super) async fn start(&self, param: RunCommandParam) -> Result<u32, String> {
+    pub(super) async fn start(
+        &self,
+        param: RunCommandParam,
+        job_id: Option<u64>,
+    ) -> Result<u32, String> {
         let mut process = self.inner.lock().await;
         if let Some(child) = process.child.as_mut() {
             if child
@@ -65,22 +80,25 @@ impl SharedDownloadProcess {
 
         process.outputs.clear();
         process.next_output_id = 0;
+        process.progress = None;
 
         let settings = Settings::new();
-        let (yt_dlp_path, _ffmpeg_path, _deno_path) = resolve_tool_paths(
-            settings.use_bundle_tools,
-            &settings.yt_dlp_path,
-            &settings.ffmpeg_path,
-            &settings.deno_path,
-        )
-        .map_err(|e| format!("ツールパスの解決に失敗しました: {}", e))?;
-
-        if yt_dlp_path.trim().is_empty() {
+        let tools = resolve_settings_tool_paths(&settings)?;
+
+        if tools.yt_dlp.trim().is_empty() {
             return Err("yt-dlpが見つかりません".into());
         }
 
-        let args = build_yt_dlp_args(param, &settings)?;
-        let mut command = Command::new(&yt_dlp_path);
+        validate_remote_param(&param)?;
+        if let Some(save_dir) = resolve_save_dir(&param, &settings) {
+            validate_destination(&save_dir, settings.min_free_space_mb)?;
+        }
+        let encoder = required_ffmpeg_encoder(&param, &settings);
+        let args = build_yt_dlp_args(param, &settings, &tools)?;
+        if let Some(encoder) = encoder {
+            ensure_ffmpeg_encoder(&tools.ffmpeg, encoder)?;
+        }
+        let mut command = Command::new(&tools.yt_dlp);
         command
             .args(&args)
             .stdout(Stdio::piped())
@@ -107,12 +125,19 @@ impl SharedDownloadProcess {
         });
 
         process.running = true;
+        process.job_id = job_id;
         process.child = Some(child);
         Ok(pid)
     }
 
-    pub(super) async fn stop(&self) -> Result<(), String> {
+    // job_idを指定したときは、そのジョブを実行中の場合だけ止める
+    pub(super) async fn stop(&self, job_id: Option<u64>) -> Result<(), String> {
         let mut process = self.inner.lock().await;
+        if let Some(job_id) = job_id {
+            if process.child.is_none() || process.job_id != Some(job_id) {
+                return Err(format!("ジョブ{}は実行されていません", job_id));
+            }
+        }
         let Some(child) = process.child.as_mut() else {
             return Err("プロセスは実行されていません".to_string());
         };
@@ -138,17 +163,30 @@ impl SharedDownloadProcess {
         }
         ProcessSnapshot {
             running: process.running,
+            job_id: process.job_id,
             outputs: process
                 .outputs
                 .iter()
                 .filter(|output| output.id >= since)
                 .cloned()
                 .collect(),
+            progress: process.progress.clone(),
         }
     }
 
     async fn push_output(&self, line: String) {
         let mut process = self.inner.lock().await;
+        let line = match classify_line(&line) {
+            ProgressLine::Progress(progress, formatted) => {
+                process.progress = Some(progress);
+                formatted
+            }
+            ProgressLine::Phase(progress) => {
+                process.progress = Some(progress);
+                line
+            }
+            ProgressLine::Plain => line,
+        };
         let id = process.next_output_id;
         process.next_output_id += 1;
         process.outputs.push(ProcessOutput { id, line });
@@ -1,4 +1,5 @@
 use crate::config::Settings;
+use crate::http_client::build_http_client;
 use auto_launch::{AutoLaunchBuilder, MacOSLaunchMode};
 use rand::distr::{Alphanumeric, SampleString};
 use serde::Serialize;
@@ -210,7 +211,7 @@ async fn is_local_server_healthy() -> Result<bool, String> {
     if settings.server_auth_token.trim().is_empty() {
         return Ok(false);
     }
-    let response = reqwest::Client::new()
+    let response = build_http_client(&settings)?
         .get(format!("http://127.0.0.1:{}/health", settings.server_port))
         .bearer_auth(settings.server_auth_token.trim())
         .send()
@@ -229,7 +230,7 @@ async fn shutdown_local_server_cli() -> Result<bool, String> {
     if settings.server_auth_token.trim().is_empty() {
         return Ok(false);
     }
-    let response = reqwest::Client::new()
+    let response = build_http_client(&settings)?
         .post(format!(
             "http://127.0.0.1:{}/shutdown",
             settings.server_port
@@ -58,7 +58,7 @@ pub(super) fn run_tray(
                 let download_process = download_process.clone();
                 std::thread::spawn(move || {
                     if let Ok(runtime) = tokio::runtime::Runtime::new() {
-                        let _ = runtime.block_on(download_process.stop());
+                        let _ = runtime.block_on(download_process.stop(None));
                     }
                 });
             }
@@ -0,0 +1,112 @@
+// 任意コードの文字列をシェルと同じ要領で引数に分割する。
+// Windowsのパスをそのまま貼り付けられるよう、バックスラッシュは空白・引用符・バックスラッシュの前でだけエスケープとして扱う
+pub(crate) fn split(input: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut chars = input.chars().enumerate().peekable();
+
+    while let Some((index, ch)) = chars.next() {
+        match ch {
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some((_, '\'')) => break,
+                        Some((_, c)) => current.push(c),
+                        None => return Err(unclosed_quote_error(index, '\'')),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.peek() {
+                            Some(&(_, next)) if next == '"' || next == '\\' => {
+                                current.push(next);
+                                chars.next();
+                            }
+                            _ => current.push('\\'),
+                        },
+                        Some((_, c)) => current.push(c),
+                        None => return Err(unclosed_quote_error(index, '"')),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                match chars.peek() {
+                    Some(&(_, next))
+                        if next.is_whitespace() || matches!(next, '\'' | '"' | '\\') =>
+                    {
+                        current.push(next);
+                        chars.next();
+                    }
+                    _ => current.push('\\'),
+                }
+            }
+            c => {
+                in_word = true;
+                current.push(c);
+            }
+        }
+    }
+
+    if in_word {
+        words.push(current);
+    }
+    Ok(words)
+}
+
+fn unclosed_quote_error(index: usize, quote: char) -> String {
+    format!("{}文字目の引用符({})が閉じられていません", index + 1, quote)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_quoted_and_escaped_words() {
+        let words = split(r#"-f "bv*+ba" -o 'my video.%(ext)s' a\ b "say \"hi\"" ''"#).unwrap();
+
+        assert_eq!(
+            words,
+            vec![
+                "-f",
+                "bv*+ba",
+                "-o",
+                "my video.%(ext)s",
+                "a b",
+                "say \"hi\"",
+                ""
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_windows_path_backslashes() {
+        let words = split(r#"--paths C:\Users\me\Videos "D:\yt dlp\out""#).unwrap();
+
+        assert_eq!(
+            words,
+            vec!["--paths", r"C:\Users\me\Videos", r"D:\yt dlp\out"]
+        );
+    }
+
+    #[test]
+    fn reports_position_of_unclosed_quote() {
+        assert_eq!(
+            split(r#"-f "best"#).unwrap_err(),
+            "4文字目の引用符(\")が閉じられていません"
+        );
+    }
+}
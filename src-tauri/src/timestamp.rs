@@ -0,0 +1,168 @@
+// 区間指定で使う時刻。秒数、mm:ss、hh:mm:ss.mmmを受け付け、先頭の-は終端からの時間を表す
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Timestamp {
+    pub seconds: f64,
+    pub from_end: bool,
+}
+
+impl Timestamp {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let trimmed = input.trim().replace('：', ":");
+        let (from_end, value) = match trimmed.strip_prefix('-') {
+            Some(value) => (true, value),
+            None => (false, trimmed.as_str()),
+        };
+        let invalid = || format!("時間の形式が不正です: {}", input.trim());
+        if value.is_empty() {
+            return Err(invalid());
+        }
+
+        let parts = value.split(':').collect::<Vec<_>>();
+        if parts.len() > 3 {
+            return Err(invalid());
+        }
+        let (last, units) = parts.split_last().ok_or_else(invalid)?;
+        let seconds = parse_seconds(last).ok_or_else(invalid)?;
+        let mut total = seconds;
+        for (index, unit) in units.iter().rev().enumerate() {
+            let unit = parse_unit(unit).ok_or_else(invalid)?;
+            total += unit as f64 * 60f64.powi(index as i32 + 1);
+        }
+        // mm:ssやhh:mm:ssの秒と分は60未満でなければならない
+        if !units.is_empty() && seconds >= 60.0 {
+            return Err(invalid());
+        }
+        if units.len() == 2 && parse_unit(units[1]).is_some_and(|minutes| minutes >= 60) {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            seconds: total,
+            from_end,
+        })
+    }
+
+    // yt-dlpの--download-sectionsに渡す hh:mm:ss(.mmm) の形式
+    pub fn to_section_time(self) -> String {
+        let total_millis = (self.seconds * 1000.0).round() as u64;
+        let hours = total_millis / 3_600_000;
+        let minutes = total_millis % 3_600_000 / 60_000;
+        let seconds = total_millis % 60_000 / 1000;
+        let millis = total_millis % 1000;
+        let sign = if self.from_end { "-" } else { "" };
+        if millis == 0 {
+            format!("{}{:02}:{:02}:{:02}", sign, hours, minutes, seconds)
+        } else {
+            format!(
+                "{}{:02}:{:02}:{:02}.{:03}",
+                sign, hours, minutes, seconds, millis
+            )
+        }
+    }
+}
+
+fn parse_unit(value: &str) -> Option<u64> {
+    if value.is_empty() || !value.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+fn parse_seconds(value: &str) -> Option<f64> {
+    let (whole, fraction) = match value.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (value, None),
+    };
+    parse_unit(whole)?;
+    if let Some(fraction) = fraction {
+        parse_unit(fraction)?;
+    }
+    value.parse().ok()
+}
+
+// 開始と終了の両方が空なら区間なし。片方が空なら先頭または末尾までとする
+pub fn build_section(start: &str, end: &str) -> Result<Option<String>, String> {
+    let start = start.trim();
+    let end = end.trim();
+    if start.is_empty() && end.is_empty() {
+        return Ok(None);
+    }
+
+    let start = if start.is_empty() {
+        Timestamp {
+            seconds: 0.0,
+            from_end: false,
+        }
+    } else {
+        Timestamp::parse(start)?
+    };
+    let end = if end.is_empty() {
+        None
+    } else {
+        Some(Timestamp::parse(end)?)
+    };
+
+    if let Some(end) = end {
+        // 同じ基準の時刻どうしでなければ、動画の長さが分からないと比較できない
+        let is_reversed = match (start.from_end, end.from_end) {
+            (false, false) => end.seconds <= start.seconds,
+            (true, true) => end.seconds >= start.seconds,
+            _ => false,
+        };
+        if is_reversed {
+            return Err(format!(
+                "終了時間は開始時間より後にしてください: {}-{}",
+                start.to_section_time(),
+                end.to_section_time()
+            ));
+        }
+    }
+
+    let end = end
+        .map(Timestamp::to_section_time)
+        .unwrap_or_else(|| "inf".to_string());
+    Ok(Some(format!("*{}-{}", start.to_section_time(), end)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_supported_formats() {
+        assert_eq!(Timestamp::parse("90").unwrap().seconds, 90.0);
+        assert_eq!(Timestamp::parse("1:30").unwrap().seconds, 90.0);
+        assert_eq!(Timestamp::parse("01:02:03.5").unwrap().seconds, 3723.5);
+        assert_eq!(
+            Timestamp::parse("-5:00").unwrap(),
+            Timestamp {
+                seconds: 300.0,
+                from_end: true
+            }
+        );
+        assert_eq!(
+            Timestamp::parse("1:75").unwrap_err(),
+            "時間の形式が不正です: 1:75"
+        );
+        assert!(Timestamp::parse("1::2").is_err());
+        assert!(Timestamp::parse("abc").is_err());
+    }
+
+    #[test]
+    fn builds_sections_and_rejects_reversed_ranges() {
+        assert_eq!(
+            build_section("1:00", "2:00.25").unwrap().unwrap(),
+            "*00:01:00-00:02:00.250"
+        );
+        assert_eq!(
+            build_section("", "-0:30").unwrap().unwrap(),
+            "*00:00:00--00:00:30"
+        );
+        assert_eq!(build_section("10", "").unwrap().unwrap(), "*00:00:10-inf");
+        assert_eq!(
+            build_section("2:00", "1:00").unwrap_err(),
+            "終了時間は開始時間より後にしてください: 00:02:00-00:01:00"
+        );
+        assert_eq!(build_section(" ", "").unwrap(), None);
+    }
+}
@@ -3,4 +3,4 @@ mod manifest;
 mod path;
 
 pub use download::{download_bundle_tools, ensure_bundle_tools};
-pub use path::{check_tools_status, resolve_tool_paths};
+pub use path::{check_tools_status, ensure_ffmpeg_encoder, resolve_settings_tool_paths, ToolPaths};
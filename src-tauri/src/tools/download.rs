@@ -1,4 +1,5 @@
 use crate::config::{AppState, VerifyCache};
+use crate::http_client::build_http_client;
 use crate::tools::manifest::{
     artifact_filename, find_tool_artifact, load_tools_manifest_from_release, sha256_file,
 };
@@ -28,8 +29,9 @@ pub async fn download_bundle_tools(
             .map_err(|e| format!("Failed to create binaries directory: {}", e))?;
     }
 
+    let client = build_http_client(&app_state.settings.lock().await.clone())?;
     let app_version = env!("CARGO_PKG_VERSION");
-    let manifest = load_tools_manifest_from_release(app_version).await?;
+    let manifest = load_tools_manifest_from_release(&client, app_version).await?;
 
     window
         .emit(
@@ -47,7 +49,7 @@ pub async fn download_bundle_tools(
         .get("yt-dlp")
         .and_then(|tool| tool.version.as_deref());
     let yt_dlp_artifact = find_tool_artifact(&manifest, "yt-dlp", os_type, arch)?;
-    install_yt_dlp_artifact(yt_dlp_artifact, &binaries_dir, &window).await?;
+    install_yt_dlp_artifact(&client, yt_dlp_artifact, &binaries_dir, &window).await?;
     let (yt_path, _, _) = resolve_tool_paths(true, "", "", "")?;
     write_tool_installed_version(&app_state, "yt-dlp", &yt_path, yt_dlp_version).await?;
     emit_download_progress(&window, "yt-dlp", 100.0, "完了");
@@ -71,6 +73,7 @@ pub async fn download_bundle_tools(
     let ffmpeg_filename = artifact_filename(ffmpeg_artifact)?;
     let ffmpeg_archive_path = binaries_dir.join(ffmpeg_filename);
     download_file_with_progress(
+        &client,
         &ffmpeg_artifact.url,
         &ffmpeg_archive_path,
         &window,
@@ -126,6 +129,7 @@ pub async fn download_bundle_tools(
     let deno_filename = artifact_filename(deno_artifact)?;
     let deno_archive_path = binaries_dir.join(deno_filename);
     download_file_with_progress(
+        &client,
         &deno_artifact.url,
         &deno_archive_path,
         &window,
@@ -168,8 +172,9 @@ pub async fn ensure_bundle_tools(
         return Ok("skipped".to_string());
     }
 
+    let client = build_http_client(&app_state.settings.lock().await.clone())?;
     let app_version = env!("CARGO_PKG_VERSION");
-    let manifest = load_tools_manifest_from_release(app_version).await?;
+    let manifest = load_tools_manifest_from_release(&client, app_version).await?;
 
     let os_type = std::env::consts::OS;
     let arch = std::env::consts::ARCH;
@@ -217,7 +222,7 @@ pub async fn ensure_bundle_tools(
                 )
                 .ok();
             let art = find_tool_artifact(&manifest, "yt-dlp", os_type, arch)?;
-            install_yt_dlp_artifact(art, &binaries_dir, &window).await?;
+            install_yt_dlp_artifact(&client, art, &binaries_dir, &window).await?;
             let (yt_path, _, _) = resolve_tool_paths(true, "", "", "")?;
             write_tool_installed_version(&app_state, "yt-dlp", &yt_path, tool.version.as_deref())
                 .await?;
@@ -265,6 +270,7 @@ pub async fn ensure_bundle_tools(
             let filename = artifact_filename(art)?;
             let archive_path = binaries_dir.join(filename);
             download_file_with_progress(
+                &client,
                 &art.url,
                 &archive_path,
                 &window,
@@ -307,6 +313,7 @@ pub async fn ensure_bundle_tools(
             let filename = artifact_filename(art)?;
             let archive_path = binaries_dir.join(&filename);
             download_file_with_progress(
+                &client,
                 &art.url,
                 &archive_path,
                 &window,
@@ -359,6 +366,7 @@ pub async fn ensure_bundle_tools(
 }
 
 async fn download_file_with_progress(
+    client: &reqwest::Client,
     url: &str,
     path: &std::path::Path,
     window: &Window,
@@ -368,7 +376,9 @@ async fn download_file_with_progress(
     let download_path = temporary_download_path(path);
     TokioFs::remove_file(&download_path).await.ok();
 
-    let response = reqwest::get(url)
+    let response = client
+        .get(url)
+        .send()
         .await
         .map_err(|e| format!("Failed to download {}: {}", tool_name, e))?;
 
@@ -462,6 +472,7 @@ async fn download_file_with_progress(
 }
 
 async fn install_yt_dlp_artifact(
+    client: &reqwest::Client,
     artifact: &crate::tools::manifest::ToolArtifact,
     binaries_dir: &std::path::Path,
     window: &Window,
@@ -474,6 +485,7 @@ async fn install_yt_dlp_artifact(
         let next_internal_dir = binaries_dir.join("_internal.new");
         let next_executable_path = binaries_dir.join("yt-dlp_macos.new");
         download_file_with_progress(
+            client,
             &artifact.url,
             &archive_path,
             window,
@@ -562,6 +574,7 @@ async fn install_yt_dlp_artifact(
         "yt-dlp"
     });
     download_file_with_progress(
+        client,
         &artifact.url,
         &executable_path,
         window,
@@ -86,10 +86,13 @@ pub(crate) fn tools_manifest_download_url(app_version: &str) -> String {
 }
 
 pub(crate) async fn load_tools_manifest_from_release(
+    client: &reqwest::Client,
     app_version: &str,
 ) -> Result<ToolsManifest, String> {
     let url = tools_manifest_download_url(app_version);
-    let response = reqwest::get(&url)
+    let response = client
+        .get(&url)
+        .send()
         .await
         .map_err(|e| format!("Failed to download tools-manifest.json: {}", e))?;
 
@@ -1,4 +1,4 @@
-use crate::config::{AppState, ToolCacheEntry, VerifyCache};
+use crate::config::{AppState, Settings, ToolCacheEntry, VerifyCache};
 use serde::Serialize;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
@@ -26,6 +26,13 @@ pub struct ToolStatus {
     deno_error: Option<String>,
 }
 
+#[derive(Clone, Default)]
+pub struct ToolPaths {
+    pub yt_dlp: String,
+    pub ffmpeg: String,
+    pub deno: String,
+}
+
 #[derive(Serialize, Clone)]
 pub(crate) struct DownloadProgress {
     pub(crate) tool_name: String,
@@ -348,6 +355,29 @@ pub(crate) fn run_tool_version(path: &str, arg: &str) -> Option<String> {
     Some(s)
 }
 
+// ffmpeg -encodersの一覧から、指定したエンコーダーが使えるかを調べる
+fn lists_encoder(encoders_output: &str, encoder: &str) -> bool {
+    encoders_output.lines().any(|line| {
+        let mut columns = line.split_whitespace();
+        matches!(
+            (columns.next(), columns.next()),
+            (Some(flags), Some(name)) if flags.len() == 6 && flags.starts_with('V') && name == encoder
+        )
+    })
+}
+
+pub fn ensure_ffmpeg_encoder(ffmpeg_path: &str, encoder: &str) -> Result<(), String> {
+    let output = run_tool_version(ffmpeg_path, "-encoders")
+        .ok_or_else(|| "ffmpegのエンコーダー一覧を取得できません".to_string())?;
+    if !lists_encoder(&output, encoder) {
+        return Err(format!(
+            "使用中のffmpegは{}に対応していません。別のコーデックを選ぶか、対応したffmpegを設定してください",
+            encoder
+        ));
+    }
+    Ok(())
+}
+
 pub fn resolve_tool_paths(
     use_bundle_tools: bool,
     yt_dlp_path: &str,
@@ -426,6 +456,21 @@ pub fn resolve_tool_paths(
     ))
 }
 
+pub fn resolve_settings_tool_paths(settings: &Settings) -> Result<ToolPaths, String> {
+    let (yt_dlp, ffmpeg, deno) = resolve_tool_paths(
+        settings.use_bundle_tools,
+        &settings.yt_dlp_path,
+        &settings.ffmpeg_path,
+        &settings.deno_path,
+    )
+    .map_err(|e| format!("ツールパスの解決に失敗しました: {}", e))?;
+    Ok(ToolPaths {
+        yt_dlp,
+        ffmpeg,
+        deno,
+    })
+}
+
 #[tauri::command]
 pub async fn check_tools_status(
     app_state: State<'_, AppState>,
@@ -469,3 +514,18 @@ pub async fn check_tools_status(
         deno_error: deno_check.err(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_video_encoders_in_ffmpeg_listing() {
+        let output = "Encoders:\n V..... = Video\n ------\n V....D libx264              libx264 H.264 / AVC\n A....D aac                  AAC (Advanced Audio Coding)\n V....D prores_ks            Apple ProRes (iCodec Pro)\n";
+
+        assert!(lists_encoder(output, "libx264"));
+        assert!(lists_encoder(output, "prores_ks"));
+        assert!(!lists_encoder(output, "aac"));
+        assert!(!lists_encoder(output, "libx265"));
+    }
+}
@@ -1,13 +1,23 @@
-use tauri::AppHandle;
+use tauri::{AppHandle, State};
 use tauri_plugin_updater::UpdaterExt;
 
+use crate::config::AppState;
+use crate::http_client::proxy_url;
 use crate::server_cli_service;
 
 #[tauri::command]
-pub async fn install_available_update(app: AppHandle) -> Result<(), String> {
-    let updater = app
+pub async fn install_available_update(
+    app: AppHandle,
+    app_state: State<'_, AppState>,
+) -> Result<(), String> {
+    let proxy = proxy_url(&app_state.settings.lock().await.proxy)?;
+    let mut updater_builder = app
         .updater_builder()
-        .on_before_exit(server_cli_service::stop_server_cli_before_exit)
+        .on_before_exit(server_cli_service::stop_server_cli_before_exit);
+    if let Some(proxy) = proxy {
+        updater_builder = updater_builder.proxy(proxy);
+    }
+    let updater = updater_builder
         .build()
         .map_err(|e| format!("アップデートの準備に失敗しました: {}", e))?;
     let update = updater
@@ -1,3 +1,10 @@
+import type {
+	ConvertSettings,
+	CookieSettings,
+	EmbedSettings,
+	SponsorBlockSettings,
+} from "../types";
+
 export interface DownloadParam {
 	codec_id?: string;
 	subtitle_lang?: string;
@@ -26,6 +33,68 @@ export interface RunCommandParam extends DownloadParam {
 	kind: DownloadModeValue;
 	url?: string;
 	arbitrary_code?: string;
+	embed?: EmbedSettings;
+	sponsorblock?: SponsorBlockSettings;
+	subtitles?: SubtitleOptions;
+	cookies?: CookieSettings;
+	sections?: TimeRange[];
+	chapter_sections?: string[];
+	force_keyframes_at_cuts?: boolean;
+	live?: LiveRecordingOptions;
+	// ffmpegの追加引数は設定の値だけが使われる
+	convert?: Omit<ConvertSettings, "postprocessor_args">;
+	extra_urls?: string[];
+	playlist?: PlaylistOptions;
+	save_dir?: string | null;
+}
+
+export interface PlaylistOptions {
+	items: string;
+	reverse: boolean;
+	max_downloads?: number | null;
+	// YYYYMMDD、またはtoday-2weeksのような相対指定
+	date_after: string;
+	date_before: string;
+	min_duration_secs?: number | null;
+	max_duration_secs?: number | null;
+	min_views?: number | null;
+	max_views?: number | null;
+	indexed_folder: boolean;
+}
+
+export interface LiveRecordingOptions {
+	wait_for_video: boolean;
+	retry_interval_secs: number;
+	max_duration_secs?: number | null;
+	// UNIX時間(秒)
+	end_at?: number | null;
+}
+
+export interface TimeRange {
+	start: string;
+	end: string;
+}
+
+export interface SubtitleOptions {
+	source: "manual" | "auto" | "both";
+	languages: string[];
+	convert: "" | "srt" | "ass" | "vtt" | "lrc";
+	embed: boolean;
+}
+
+export interface StartedDownload {
+	jobId: number;
+	pid: number;
+}
+
+export interface ProcessOutputPayload {
+	jobId: number;
+	line: string;
+}
+
+export interface ProcessExitPayload {
+	jobId: number;
+	message: string;
 }
 
 export type TimestampField = "start_time" | "end_time";
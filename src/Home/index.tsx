@@ -27,9 +27,12 @@ import {
 	isDownloadModeValue,
 	isValidTimestamp,
 	normalizeTimestamp,
+	type ProcessExitPayload,
+	type ProcessOutputPayload,
 	parseQueueItems,
 	type RunCommandParam,
 	resolveOutputName,
+	type StartedDownload,
 	shortenText,
 	type TimestampField,
 } from "./downloadForm";
@@ -76,6 +79,7 @@ export default function Home() {
 		useCookie,
 	} = useAppContext();
 	const [pid, setPid] = useState<number | null>(null);
+	const jobIdRef = useRef<number | null>(null);
 	const [consoleText, setConsoleText] = useState("");
 	const [urlInput, setUrlInput] = useState("");
 	const [arbitraryCode, setArbitraryCode] = useState("");
@@ -183,10 +187,11 @@ export default function Home() {
 			arbitrary_code: arbitraryCode,
 			kind: currentSelectedIndex,
 		};
-		const processId = await invoke<number>("start_download", {
+		const started = await invoke<StartedDownload>("start_download", {
 			param: runParam,
 		});
-		setPid(processId);
+		jobIdRef.current = started.jobId;
+		setPid(started.pid);
 	}, [arbitraryCode, hasInvalidTimestamp, param]);
 
 	const runCommandFromUrl = useCallback(
@@ -221,10 +226,11 @@ export default function Home() {
 				url,
 				kind: currentSelectedIndex,
 			};
-			const processId = await invoke<number>("start_download", {
+			const started = await invoke<StartedDownload>("start_download", {
 				param: runParam,
 			});
-			setPid(processId);
+			jobIdRef.current = started.jobId;
+			setPid(started.pid);
 		},
 		[param],
 	);
@@ -304,45 +310,65 @@ export default function Home() {
 	}, [resetQueueState, runCommandFromUrl]);
 
 	useEffect(() => {
-		const unlistenOutput = listen<string>("process-output", (event) => {
-			if (event.payload === "") {
-				return;
-			}
-			if (event.payload.includes("Destination:")) {
-				latestDownloadDestinationRef.current = event.payload;
-			}
-			if (
-				event.payload.startsWith("[download]") ||
-				event.payload.startsWith("[Merger]") ||
-				event.payload.startsWith("[Fixup")
-			) {
-				const progressPayload =
-					latestDownloadDestinationRef.current &&
-					event.payload.startsWith("[download]") &&
-					!event.payload.includes("Destination:")
-						? `${event.payload}\n${latestDownloadDestinationRef.current}`
-						: event.payload;
-				setLatestConsoleText(progressPayload);
-			}
-			setConsoleText((prev) => {
-				if (prev === "") {
-					return event.payload.trimStart();
+		const unlistenOutput = listen<ProcessOutputPayload>(
+			"process-output",
+			(event) => {
+				if (
+					jobIdRef.current !== null &&
+					event.payload.jobId !== jobIdRef.current
+				) {
+					return;
 				}
-				return `${prev}\n${event.payload}`;
-			});
-		});
+				const line = event.payload.line;
+				if (line === "") {
+					return;
+				}
+				if (line.includes("Destination:")) {
+					latestDownloadDestinationRef.current = line;
+				}
+				if (
+					line.startsWith("[download]") ||
+					line.startsWith("[Merger]") ||
+					line.startsWith("[Fixup")
+				) {
+					const progressPayload =
+						latestDownloadDestinationRef.current &&
+						line.startsWith("[download]") &&
+						!line.includes("Destination:")
+							? `${line}\n${latestDownloadDestinationRef.current}`
+							: line;
+					setLatestConsoleText(progressPayload);
+				}
+				setConsoleText((prev) => {
+					if (prev === "") {
+						return line.trimStart();
+					}
+					return `${prev}\n${line}`;
+				});
+			},
+		);
 
-		const unlistenExit = listen<string>("process-exit", () => {
-			const wasStopped = stopRequestedRef.current;
-			stopRequestedRef.current = false;
-			latestDownloadDestinationRef.current = "";
-			setLatestConsoleText(wasStopped ? DOWNLOAD_STOPPED_MESSAGE : "");
-			setPid(null);
-			if (wasStopped) {
-				return;
-			}
-			runQueueNext();
-		});
+		const unlistenExit = listen<ProcessExitPayload>(
+			"process-exit",
+			(event) => {
+				if (
+					jobIdRef.current !== null &&
+					event.payload.jobId !== jobIdRef.current
+				) {
+					return;
+				}
+				jobIdRef.current = null;
+				const wasStopped = stopRequestedRef.current;
+				stopRequestedRef.current = false;
+				latestDownloadDestinationRef.current = "";
+				setLatestConsoleText(wasStopped ? DOWNLOAD_STOPPED_MESSAGE : "");
+				setPid(null);
+				if (wasStopped) {
+					return;
+				}
+				runQueueNext();
+			},
+		);
 
 		return () => {
 			unlistenOutput.then((fn) => fn());
@@ -375,7 +401,7 @@ export default function Home() {
 		resetQueueState();
 		stopRequestedRef.current = true;
 		try {
-			await invoke("stop_download");
+			await invoke("stop_download", { jobId: jobIdRef.current });
 		} catch (error) {
 			stopRequestedRef.current = false;
 			toast.error(`停止に失敗しました:${stringifyError(error)}`);
@@ -1,3 +1,120 @@
+export interface VideoFormatSettings {
+	strategy: "generic" | "youtube_legacy";
+	preferred_vcodec: "" | "h264" | "h265" | "vp9" | "av01";
+	preferred_container: "" | "mp4" | "webm" | "mkv";
+	max_fps: number;
+	prefer_hdr: boolean;
+}
+
+export interface AudioFormatSettings {
+	codec:
+		| "original"
+		| "mp3"
+		| "m4a"
+		| "aac"
+		| "opus"
+		| "vorbis"
+		| "flac"
+		| "wav";
+	quality: string;
+	normalize: boolean;
+}
+
+export interface EmbedSettings {
+	metadata: boolean;
+	thumbnail: boolean;
+	thumbnail_format: "" | "jpg" | "png" | "webp";
+	chapters: boolean;
+	subtitles: boolean;
+	split_chapters: boolean;
+}
+
+export interface SponsorBlockSettings {
+	enabled: boolean;
+	remove: string[];
+	mark: string[];
+	api_url: string;
+}
+
+export interface DownloadArchiveSettings {
+	enabled: boolean;
+	scope: "global" | "save_dir";
+}
+
+export interface NetworkSettings {
+	rate_limit: string;
+	retries: number;
+	fragment_retries: number;
+	concurrent_fragments: number;
+	socket_timeout: number;
+	throttled_rate: string;
+}
+
+export interface ProxySettings {
+	enabled: boolean;
+	url: string;
+	username: string;
+	password: string;
+	no_proxy: string[];
+}
+
+export interface CookieSettings {
+	source: "browser" | "file";
+	file_path: string;
+	profile: string;
+	container: string;
+	keyring:
+		| ""
+		| "basictext"
+		| "gnomekeyring"
+		| "kwallet"
+		| "kwallet5"
+		| "kwallet6";
+}
+
+export interface ConvertSettings {
+	mode: "none" | "remux" | "recode";
+	container: "mp4" | "mkv" | "mov" | "webm" | "avi" | "flv";
+	video_codec: "" | "h264" | "h265" | "vp9" | "av1" | "prores";
+	postprocessor_args: string;
+}
+
+export interface FilenameSettings {
+	restrict: boolean;
+	windows_safe: boolean;
+}
+
+export interface JsRuntimeSettings {
+	mode: "remote" | "bundled" | "local" | "disabled";
+	component_dir: string;
+}
+
+export interface ModeSaveDirs {
+	video: string;
+	audio: string;
+	live: string;
+	thumbnail: string;
+	subtitle: string;
+	arbitrary_code: string;
+}
+
+export interface SiteRuleOverrides {
+	use_cookie?: boolean | null;
+	browser?: string | null;
+	video_format?: VideoFormatSettings | null;
+	output_template?: string | null;
+	save_dir?: string | null;
+	extra_args?: string | null;
+}
+
+export interface SiteRule {
+	name: string;
+	enabled: boolean;
+	match_type: "domain" | "regex";
+	pattern: string;
+	overrides: SiteRuleOverrides;
+}
+
 export interface ConfigProps {
 	save_dir: string;
 	browser: string;
@@ -14,4 +131,20 @@ export interface ConfigProps {
 	remote_server_url: string;
 	remote_auth_token: string;
 	server_auth_token: string;
+	max_concurrent_downloads: number;
+	video_format: VideoFormatSettings;
+	audio_format: AudioFormatSettings;
+	embed: EmbedSettings;
+	sponsorblock: SponsorBlockSettings;
+	arbitrary_code_denied_flags: string[];
+	download_archive: DownloadArchiveSettings;
+	network: NetworkSettings;
+	proxy: ProxySettings;
+	cookies: CookieSettings;
+	convert: ConvertSettings;
+	filename: FilenameSettings;
+	mode_save_dirs: ModeSaveDirs;
+	js_runtime: JsRuntimeSettings;
+	min_free_space_mb: number;
+	site_rules: SiteRule[];
 }